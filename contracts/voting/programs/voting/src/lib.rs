@@ -1,55 +1,409 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::{hash, hashv};
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
 
 // Program ID
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkgMQoezjGvEJ");
 
+// Minimum turnout required for a question's result to count
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum QuorumRule {
+    #[default]
+    None,                          // Any turnout is enough
+    Absolute { min_ballots: u32 }, // At least this many ballots
+    PercentOfMembers { bps: u16 }, // At least this share of member_count, in basis points
+}
+
+// Share of the vote the leading option needs to pass
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum PassThreshold {
+    #[default]
+    SimpleMajority,             // Strictly more than half of the ballots
+    Supermajority { bps: u16 }, // At least this share of the ballots, in basis points
+}
+
+// How much a member's ballot counts for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum VoteWeighting {
+    #[default]
+    OnePerMember,                  // Every ballot counts once
+    TokenBalance { mint: Pubkey }, // Ballots weigh the voter's balance of this SPL mint
+    LockedTokens { mint: Pubkey }, // Ballots weigh the tokens the voter has locked in escrow
+}
+
 // Community configuration struct
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
 pub struct CommunityConfig {
-    pub voting_period: i64, // Voting period in seconds
-    pub max_options: u8,    // Maximum number of options per question (e.g., 4)
+    pub voting_period: i64,            // Default and longest question duration, in seconds
+    pub min_voting_period: i64,        // Shortest question duration, in seconds
+    pub max_options: u8,               // Maximum number of options per question (e.g., 4)
+    pub quorum: QuorumRule,            // Minimum turnout
+    pub pass_threshold: PassThreshold, // Support needed for the leading option to pass
+    pub vote_weighting: VoteWeighting, // One member one vote, or token-weighted
+}
+
+// Limits on variable-length fields. Account sizes are derived from these through InitSpace.
+const MAX_NAME_LEN: usize = 32;
+const MAX_DESCRIPTION_LEN: usize = 256;
+const MAX_SLUG_LEN: usize = 32; // In bytes; the PDA seed is its hash
+const MAX_QUESTION_LEN: usize = 256;
+const MAX_OPTIONS: usize = 4;
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTION_LEN: usize = 32;
+const MAX_CANCEL_REASON_LEN: usize = 128;
+const MAX_ADMINS: usize = 5;
+
+// Shortest voting window for a question carrying a community action, in seconds
+const MIN_ACTION_VOTING_PERIOD: i64 = 24 * 60 * 60;
+// How long after voting ends a passed action may still be executed, in seconds
+const ACTION_EXECUTION_WINDOW: i64 = 7 * 24 * 60 * 60;
+
+// Lifecycle of a community
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, Debug, InitSpace)]
+pub enum CommunityStatus {
+    #[default]
+    Active,    // Normal operation
+    Paused,    // Admin has temporarily halted joins, new questions and voting
+    Dissolved, // Permanently closed
+}
+
+// Lifecycle of a membership. Variant order matches the old u8 codes, so the
+// one-byte Borsh encoding of existing Member accounts is unchanged.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, Debug, InitSpace)]
+pub enum MemberStatus {
+    #[default]
+    Pending,  // Requested to join, awaiting the admin
+    Approved, // Full member
+    Rejected, // Request turned down; may re-apply
+    Removed,  // Removed by the admin; may re-apply
+}
+
+// Member roles, each a preset of permissions. Member and Admin keep their old u8 codes
+// (0 and 1), so the one-byte Borsh encoding of existing Member accounts is unchanged.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, Debug, InitSpace)]
+pub enum MemberRole {
+    #[default]
+    Member,
+    Admin,
+    Moderator,
+}
+
+// Member permission bits
+pub const PERMISSION_APPROVE_MEMBERS: u16 = 1 << 0;
+pub const PERMISSION_REMOVE_MEMBERS: u16 = 1 << 1;
+pub const PERMISSION_CREATE_QUESTIONS: u16 = 1 << 2;
+pub const PERMISSION_CLOSE_QUESTIONS: u16 = 1 << 3;
+pub const PERMISSION_EDIT_CONFIG: u16 = 1 << 4;
+pub const PERMISSION_MANAGE_ROLES: u16 = 1 << 5;
+pub const PERMISSION_PROPOSE_ACTIONS: u16 = 1 << 6; // Attach a community action to a question
+pub const ALL_PERMISSIONS: u16 = (1 << 7) - 1;
+
+// Permissions granted by each role
+pub fn role_permissions(role: MemberRole) -> u16 {
+    match role {
+        MemberRole::Member => PERMISSION_CREATE_QUESTIONS,
+        MemberRole::Moderator => {
+            PERMISSION_APPROVE_MEMBERS
+                | PERMISSION_REMOVE_MEMBERS
+                | PERMISSION_CREATE_QUESTIONS
+                | PERMISSION_CLOSE_QUESTIONS
+        }
+        MemberRole::Admin => ALL_PERMISSIONS,
+    }
+}
+
+// An admin handing their seat to another wallet, waiting for that wallet to accept
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug, InitSpace)]
+pub struct AdminTransfer {
+    pub from: Pubkey,             // Admin giving up their seat
+    pub to: Pubkey,               // Wallet that must accept
+    pub proposed_at: i64,
+    pub expires_at: Option<i64>,  // After this the transfer can no longer be accepted
+}
+
+// Administrative change to a community, applied when an AdminProposal or a passed
+// governance question is executed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub enum CommunityAction {
+    UpdateConfig { config: CommunityConfig },
+    RemoveMember { wallet: Pubkey },
+    ReplaceAdmin { old_admin: Pubkey, new_admin: Pubkey }, // Hand an admin seat to another wallet
+    DissolveCommunity,
+    SetAdminThreshold { threshold: u8 }, // Approvals needed for later proposals
+    AddAdmin { wallet: Pubkey },          // Must be an approved member
+    RemoveAdmin { wallet: Pubkey },
+    ChangeMemberRole { wallet: Pubkey, role: MemberRole },
+    SetMemberPermissions { wallet: Pubkey, permissions: u16 },
 }
 
 // Community account
 #[account]
+#[derive(InitSpace)]
 pub struct Community {
-    pub admin: Pubkey,           // Community admin/creator
+    pub version: u8,             // Schema version (see migrate_community)
+    #[max_len(MAX_ADMINS)]
+    pub admins: Vec<Pubkey>,     // Community admins; never empty, starts with the creator
+    #[max_len(MAX_NAME_LEN)]
     pub name: String,            // Community name
+    #[max_len(MAX_DESCRIPTION_LEN)]
     pub description: String,     // Community description
     pub member_count: u32,       // Number of approved members
     pub created_at: i64,         // Timestamp
     pub config: CommunityConfig, // Voting rules/config
+    pub question_count: u64,     // Questions created so far; seeds the next question's PDA
+    #[max_len(MAX_SLUG_LEN)]
+    pub slug: String,            // Normalized name; seeds the community PDA and reserves the name
+    pub status: CommunityStatus, // Lifecycle state
+    pub pending_admin: Option<AdminTransfer>, // Admin transfer awaiting acceptance
+    pub admin_threshold: u8,     // Admin approvals an AdminProposal needs; above 1, admins can't act alone
+    pub admin_proposal_count: u64, // Admin proposals created so far; seeds the next proposal's PDA
 }
 
 // Member account
 #[account]
+#[derive(InitSpace)]
 pub struct Member {
+    pub version: u8,       // Schema version (see migrate_member)
     pub community: Pubkey, // Reference to Community
     pub wallet: Pubkey,    // Member's wallet address
-    pub role: u8,          // 0 = member, 1 = admin
+    pub role: MemberRole,  // Member or admin
     pub joined_at: i64,    // Timestamp
-    pub status: u8,        // 0 = pending, 1 = approved, 2 = rejected
+    pub status: MemberStatus, // Pending, approved, rejected or removed
+    pub delegations_received: u32, // Members currently delegating their vote to this member
+    pub delegations_given: u32,    // Delegations this member currently holds (global and per question)
+    pub permissions: u16,  // PERMISSION_* bits; the role's preset unless customised
+}
+
+// How members fill in their ballot for a question
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum BallotType {
+    #[default]
+    SingleChoice, // Exactly one option
+    MultipleChoice { min_selections: u8, max_selections: u8 }, // Approval voting over a set of options
+    RankedChoice, // Full preference ordering, resolved by instant runoff
+    Quadratic { voice_credits: u32 }, // Votes spread across options, n votes on an option cost n^2 credits
 }
 
 // VotingQuestion account
 #[account]
+#[derive(InitSpace)]
 pub struct VotingQuestion {
+    pub version: u8,           // Schema version (see migrate_voting_question)
     pub community: Pubkey,     // Reference to Community
     pub creator: Pubkey,       // Creator of the question
+    #[max_len(MAX_QUESTION_LEN)]
     pub question: String,      // The question text
+    #[max_len(MAX_OPTIONS, MAX_OPTION_LEN)]
     pub options: Vec<String>,  // Up to 4 options
     pub deadline: i64,         // Voting deadline (timestamp)
     pub created_at: i64,       // Timestamp
     pub is_active: bool,       // Is the question active?
+    #[max_len(MAX_OPTIONS)]
+    pub vote_counts: Vec<u64>, // Running tally, one counter per option
+    pub total_ballots: u64,    // Number of ballots cast
+    pub eligible_voters: u32,  // Community member_count when the question was created
+    pub is_finalized: bool,    // Has a QuestionResult been written?
+    pub quorum: QuorumRule,    // Community quorum rule when the question was created
+    pub pass_threshold: PassThreshold, // Community pass threshold when the question was created
+    pub ballot_type: BallotType, // Single choice or multiple choice (approval)
+    pub vote_weighting: VoteWeighting, // Community vote weighting when the question was created
+    pub total_weight: u64,     // Sum of ballot weights cast
+    pub reveal_deadline: Option<i64>, // Commit-reveal: commitments are revealed between deadline and this
+    pub total_commits: u64,    // Commit-reveal: commitments submitted (revealed ones count in total_ballots)
+    pub index: u64,            // Position in the community's question sequence (PDA seed)
+    pub starts_at: Option<i64>, // Ballots are accepted from this time; None opens voting at creation
+    pub action: Option<CommunityAction>, // Governance: applied if option 0 (approve) passes
+    pub action_executed: bool, // Governance: has the action been applied?
+}
+
+// Outcome of a finalized question under the community's governance rules
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum QuestionOutcome {
+    Passed,       // Quorum met and the leading option reached the pass threshold
+    Failed,       // Quorum met but no single option reached the pass threshold
+    QuorumNotMet, // Not enough ballots were cast
+    Cancelled,    // Closed early by the creator or a moderator
 }
 
 // Vote account
 #[account]
+#[derive(InitSpace)]
 pub struct Vote {
+    pub version: u8,           // Schema version (see migrate_vote)
     pub question: Pubkey,      // Reference to VotingQuestion
     pub voter: Pubkey,         // Voter's wallet address
-    pub selected_option: u8,   // Index of selected option
+    pub selected_option: u8,   // Index of selected option (first selection on multiple-choice ballots)
     pub voted_at: i64,         // Timestamp
+    #[max_len(MAX_OPTIONS)]
+    pub selected_options: Vec<u8>, // Every option this ballot counts towards (preference order for ranked ballots)
+    pub tallied_round: u8,     // Next instant-runoff round this ballot is due to be counted in
+    pub weight: u64,           // Voting weight this ballot was counted with
+    #[max_len(MAX_OPTIONS)]
+    pub allocation: Vec<u16>,  // Votes per option (quadratic ballots only)
+    pub delegated_to: Option<Pubkey>, // Set on markers created when a delegate counted this voter's weight
+    pub delegated_weight: u64, // Part of `weight` claimed from delegators
+    pub commitment: Option<[u8; 32]>, // Commit-reveal: sha256(question, voter, option, salt)
+    pub revealed: bool,        // Commit-reveal: has the commitment been opened and tallied?
+}
+
+// Current schema version of each versioned account. Bump when its layout changes,
+// freeze the old layout below and teach the matching migrate_* instruction to read it.
+const COMMUNITY_VERSION: u8 = 1;
+const MEMBER_VERSION: u8 = 1;
+const QUESTION_VERSION: u8 = 1;
+const VOTE_VERSION: u8 = 1;
+
+// Account sizes (discriminator + InitSpace), shared by the instructions that create an account and its migration
+const COMMUNITY_SPACE: usize = 8 + Community::INIT_SPACE;
+const MEMBER_SPACE: usize = 8 + Member::INIT_SPACE;
+const QUESTION_SPACE: usize = 8 + VotingQuestion::INIT_SPACE;
+const VOTE_SPACE: usize = 8 + Vote::INIT_SPACE;
+
+// --- Unversioned (version 0) layouts, kept to migrate accounts written before versioning ---
+// These are the originally deployed accounts. They carry no version byte and are recognised
+// by the fixed size they were allocated with; any other size is read as a versioned account.
+
+const COMMUNITY_V0_SPACE: usize = 368;
+const MEMBER_V0_SPACE: usize = 96;
+const QUESTION_V0_SPACE: usize = 512;
+const VOTE_V0_SPACE: usize = 96;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct CommunityConfigV0 {
+    pub voting_period: i64,
+    pub max_options: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct CommunityV0 {
+    pub admin: Pubkey,
+    pub name: String,
+    pub description: String,
+    pub member_count: u32, // u32::MAX marked a dissolved community
+    pub created_at: i64,
+    pub config: CommunityConfigV0,
+}
+
+// `role` and `status` were plain u8s whose values match the enums' variant indices
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct MemberV0 {
+    pub community: Pubkey,
+    pub wallet: Pubkey,
+    pub role: MemberRole,
+    pub joined_at: i64,
+    pub status: MemberStatus,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct VotingQuestionV0 {
+    pub community: Pubkey,
+    pub creator: Pubkey,
+    pub question: String,
+    pub options: Vec<String>,
+    pub deadline: i64,
+    pub created_at: i64,
+    pub is_active: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct VoteV0 {
+    pub question: Pubkey,
+    pub voter: Pubkey,
+    pub selected_option: u8,
+    pub voted_at: i64,
+}
+
+// Delegation account (a member handing their voting power to another member).
+// Delegation is a single hop: a delegate may not delegate onward and a member
+// holding delegations may not delegate, so chains never form and cycles are impossible.
+// A member has one global delegation and at most one per question; for that question
+// the per-question one takes precedence.
+#[account]
+#[derive(InitSpace)]
+pub struct Delegation {
+    pub community: Pubkey,        // Reference to Community
+    pub delegator: Pubkey,        // Member giving their voting power
+    pub delegate: Pubkey,         // Member receiving it
+    pub question: Option<Pubkey>, // Only this question, or every question when None
+    pub created_at: i64,          // Timestamp
+}
+
+// VoterEscrow account (tokens a member has locked in the community vault)
+#[account]
+#[derive(InitSpace)]
+pub struct VoterEscrow {
+    pub community: Pubkey,  // Reference to Community
+    pub owner: Pubkey,      // Member's wallet address
+    pub mint: Pubkey,       // Mint of the locked tokens
+    pub amount: u64,        // Tokens currently locked; this is the member's voting weight
+    pub locked_until: i64,  // Latest deadline among questions voted on with these tokens
+}
+
+// QuestionResult account (written once by finalize_question, never modified)
+#[account]
+#[derive(InitSpace)]
+pub struct QuestionResult {
+    pub question: Pubkey,         // Reference to VotingQuestion
+    pub community: Pubkey,        // Reference to Community
+    #[max_len(MAX_OPTIONS)]
+    pub winning_options: Vec<u8>, // Option(s) sharing the highest count
+    #[max_len(MAX_OPTIONS)]
+    pub vote_counts: Vec<u64>,    // Final per-option tally
+    pub total_ballots: u64,       // Number of ballots cast
+    pub total_weight: u64,        // Sum of ballot weights cast
+    pub eligible_voters: u32,     // Community member_count when the question was created
+    pub turnout_bps: u16,         // total_ballots / eligible_voters, in basis points
+    pub is_tie: bool,             // More than one option shares the highest count
+    pub outcome: QuestionOutcome, // Passed / Failed / QuorumNotMet / Cancelled
+    pub finalized_at: i64,        // Timestamp
+    #[max_len(MAX_OPTIONS)]
+    pub runoff_rounds: Vec<RunoffRound>, // Instant-runoff history (ranked-choice questions only)
+    pub unrevealed_commits: u64,  // Commit-reveal: commitments never revealed, not tallied
+}
+
+// One counted round of an instant-runoff tally
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct RunoffRound {
+    #[max_len(MAX_OPTIONS)]
+    pub vote_counts: Vec<u64>, // Ballots per option, counted at their top remaining preference
+    #[max_len(MAX_OPTIONS)]
+    pub eliminated: Vec<u8>,   // Options eliminated at the end of this round
+}
+
+// RunoffTally account (crank state while instant-runoff rounds are being counted)
+#[account]
+#[derive(InitSpace)]
+pub struct RunoffTally {
+    pub question: Pubkey,             // Reference to VotingQuestion
+    pub payer: Pubkey,                // Receives the rent back when finalize_question closes it
+    pub round: u8,                    // Round currently being counted
+    #[max_len(MAX_OPTIONS)]
+    pub eliminated: Vec<u8>,          // Options eliminated in earlier rounds
+    #[max_len(MAX_OPTIONS)]
+    pub round_counts: Vec<u64>,       // Counts accumulated so far in the current round
+    pub ballots_processed: u64,       // Ballots counted so far in the current round
+    #[max_len(MAX_OPTIONS)]
+    pub rounds: Vec<RunoffRound>,     // Completed rounds
+    #[max_len(MAX_OPTIONS)]
+    pub winning_options: Vec<u8>,     // Winner (or tied options) once complete
+    pub is_complete: bool,            // No further rounds needed
+}
+
+// AdminProposal account (an administrative action waiting for M-of-N admin approval).
+// Closed, with rent back to the proposer, once executed or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct AdminProposal {
+    pub community: Pubkey,       // Reference to Community
+    pub index: u64,              // Position in the community's proposal sequence; seeds the PDA
+    pub proposer: Pubkey,        // Admin who proposed it
+    pub action: CommunityAction, // Applied on execution
+    #[max_len(MAX_ADMINS)]
+    pub approvals: Vec<Pubkey>,  // Admins who approved, starting with the proposer
+    pub created_at: i64,         // Timestamp
+    pub expires_at: i64,         // Can't be approved or executed after this
 }
 
 // Add custom error codes for validation
@@ -61,8 +415,18 @@ pub enum VotingError {
     DescriptionTooLong,
     #[msg("Voting period must be positive")] 
     InvalidVotingPeriod,
+    #[msg("Minimum voting period must be between zero and the voting period")]
+    InvalidMinVotingPeriod,
     #[msg("Max options must be between 2 and 4")] 
     InvalidMaxOptions,
+    #[msg("Quorum must be a positive ballot count or a percentage between 0.01% and 100%")]
+    InvalidQuorum,
+    #[msg("Supermajority threshold must be above 50% and at most 100%")]
+    InvalidPassThreshold,
+    #[msg("Community counter has reached its maximum")]
+    CounterOverflow,
+    #[msg("Community name must contain at least one letter or digit")]
+    InvalidName,
 }
 
 #[error_code]
@@ -87,8 +451,18 @@ pub enum VotingQuestionError {
     QuestionTooLong,
     #[msg("Too many options")]
     TooManyOptions,
-    #[msg("Deadline must be in the future")]
+    #[msg("Option text is too long")]
+    OptionTooLong,
+    #[msg("Deadline must be in the future and after voting opens")]
     InvalidDeadline,
+    #[msg("Voting start time must not be in the past")]
+    InvalidStartTime,
+    #[msg("Voting has not opened yet")]
+    VotingNotStarted,
+    #[msg("Question is open for less than the community's minimum voting period")]
+    VotingPeriodTooShort,
+    #[msg("Question is open for longer than the community's voting period")]
+    VotingPeriodTooLong,
     #[msg("Voting question is not active")]
     NotActive,
     #[msg("Voting deadline has passed")]
@@ -97,11 +471,149 @@ pub enum VotingQuestionError {
     InvalidOption,
     #[msg("Member has already voted on this question")]
     AlreadyVoted,
+    #[msg("Vote tally overflow")]
+    TallyOverflow,
+    #[msg("Member account does not belong to the signer")]
+    WrongMember,
+    #[msg("Question does not belong to this community")]
+    WrongCommunity,
+    #[msg("Ballot does not match the question's ballot type")]
+    WrongBallotType,
+    #[msg("Selection limits must satisfy 1 <= min <= max <= number of options")]
+    InvalidSelectionLimits,
+    #[msg("Number of selected options is outside the allowed range")]
+    InvalidSelectionCount,
+    #[msg("An option was selected more than once")]
+    DuplicateOption,
+    #[msg("Quadratic questions need a positive voice credit budget")]
+    InvalidVoiceCredits,
+    #[msg("Allocation must list votes for every option and give at least one vote")]
+    InvalidAllocation,
+    #[msg("Allocation costs more voice credits than the budget")]
+    InsufficientVoiceCredits,
+}
+
+#[error_code]
+pub enum VoteChangeError {
+    #[msg("Secret ballots cannot be changed or retracted")]
+    SecretBallot,
+    #[msg("Only a member's own ballot can be changed or retracted")]
+    NotDirectVote,
+    #[msg("A ballot carrying delegated votes cannot be retracted")]
+    HoldsDelegatedVotes,
+    #[msg("Vote allocations are only accepted for quadratic questions")]
+    UnexpectedAllocation,
+    #[msg("Community does not match the question")]
+    WrongCommunity,
+}
+
+#[error_code]
+pub enum CommitRevealError {
+    #[msg("Secret ballots are only supported for single-choice questions")]
+    SingleChoiceOnly,
+    #[msg("Reveal period must not be negative")]
+    InvalidRevealPeriod,
+    #[msg("This question uses secret ballots; commit a hash instead")]
+    CommitRequired,
+    #[msg("This question does not use secret ballots")]
+    NotSecretBallot,
+    #[msg("Votes can only be revealed after the deadline and before the reveal deadline")]
+    NotRevealPeriod,
+    #[msg("No unrevealed commitment for this vote")]
+    NothingToReveal,
+    #[msg("Revealed option and salt do not match the commitment")]
+    CommitmentMismatch,
+}
+
+#[error_code]
+pub enum FinalizationError {
+    #[msg("Voting is still open for this question")]
+    VotingNotEnded,
+    #[msg("Question has already been finalized")]
+    AlreadyFinalized,
+}
+
+#[error_code]
+pub enum RunoffError {
+    #[msg("Question does not use ranked-choice ballots")]
+    NotRankedChoice,
+    #[msg("Ballot belongs to a different question")]
+    WrongQuestion,
+    #[msg("Ballot has already been counted in this round")]
+    BallotAlreadyCounted,
+    #[msg("Instant-runoff tally is already complete")]
+    RunoffComplete,
+    #[msg("Instant-runoff tally must be completed before finalizing")]
+    RunoffIncomplete,
+    #[msg("Runoff rent must be returned to the account that paid for it")]
+    WrongRunoffPayer,
+}
+
+#[error_code]
+pub enum WeightError {
+    #[msg("Token-weighted voting requires the voter's token account")]
+    MissingTokenAccount,
+    #[msg("Token account does not hold the community's voting mint")]
+    WrongMint,
+    #[msg("Token account is not owned by the voter")]
+    WrongTokenOwner,
+    #[msg("Voter has no voting power")]
+    NoVotingPower,
+    #[msg("Locked-token voting requires the voter's escrow account")]
+    MissingEscrow,
+}
+
+#[error_code]
+pub enum EscrowError {
+    #[msg("Community does not use locked-token voting")]
+    NotLockedTokens,
+    #[msg("Mint does not match the community's voting mint")]
+    WrongMint,
+    #[msg("Amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("Not enough tokens locked in escrow")]
+    InsufficientEscrow,
+    #[msg("Tokens are locked until the questions voted on have closed")]
+    TokensLocked,
+    #[msg("Escrow does not belong to this voter")]
+    WrongEscrow,
+}
+
+#[error_code]
+pub enum DelegationError {
+    #[msg("Members cannot delegate to themselves")]
+    SelfDelegation,
+    #[msg("Delegate has delegated their own vote; delegation is a single hop")]
+    DelegateHasDelegated,
+    #[msg("Member holds delegations from others and cannot delegate onward")]
+    DelegatorHoldsDelegations,
+    #[msg("Member account does not match the delegation")]
+    WrongMember,
+    #[msg("Delegation is not to this voter")]
+    WrongDelegate,
+    #[msg("Delegation does not apply to this question")]
+    OutOfScope,
+    #[msg("Delegator is not an approved member")]
+    DelegatorInactive,
+    #[msg("Delegator has already voted or been counted on this question")]
+    DelegatorAlreadyVoted,
+    #[msg("Delegation is not supported with token-balance weighting")]
+    UnsupportedWeighting,
+    #[msg("Delegated votes must be passed as complete account groups")]
+    MissingAccounts,
+    #[msg("Delegate's vote account does not match the delegation marker")]
+    WrongDelegateVote,
+    #[msg("Only a member's own ballot can claim delegated votes")]
+    NotDirectVote,
+    #[msg("Wallet does not match the overridden delegate")]
+    WrongDelegateWallet,
+    #[msg("Delegated votes cannot be claimed on secret-ballot questions")]
+    SecretBallot,
 }
 
 #[error_code]
 pub enum AdminActionError {
-    #[msg("Only the community admin can perform this action")]
+    #[msg("Only a community admin can perform this action")]
     NotAdmin,
     #[msg("Community is already dissolved")]
     CommunityDissolved,
@@ -109,6 +621,34 @@ pub enum AdminActionError {
     NotApproved,
     #[msg("Member is already removed or rejected")]
     AlreadyRemoved,
+    #[msg("Community is already paused")]
+    AlreadyPaused,
+    #[msg("Community is not paused")]
+    NotPaused,
+}
+
+#[error_code]
+pub enum PermissionError {
+    #[msg("Signer lacks the permission this action requires")]
+    MissingPermission,
+    #[msg("Cannot grant permissions the signer does not hold")]
+    PermissionEscalation,
+    #[msg("Unknown permission bits")]
+    InvalidPermissions,
+}
+
+#[error_code]
+pub enum MigrationError {
+    #[msg("Account is not of the type this migration expects")]
+    WrongAccountType,
+    #[msg("Account is already on the current schema version")]
+    AlreadyCurrent,
+    #[msg("Account has a schema version this program does not know")]
+    UnknownVersion,
+    #[msg("Migrate the account this one belongs to first")]
+    ParentNotMigrated,
+    #[msg("Account does not belong to the given parent account")]
+    WrongParent,
 }
 
 #[error_code]
@@ -119,6 +659,40 @@ pub enum EdgeCaseError {
     MemberInactive,
     #[msg("Member is already pending or approved")]
     AlreadyActive,
+    #[msg("Community is paused; action not allowed")]
+    CommunityPaused,
+}
+
+#[error_code]
+pub enum QuestionCloseError {
+    #[msg("Only the question creator or a member who can close questions can cancel a question")]
+    NotAuthorized,
+    #[msg("Voting is still open; only the creator or admin can cancel before the deadline")]
+    VotingStillOpen,
+    #[msg("Voting has ended; close and finalize the question instead")]
+    VotingEnded,
+    #[msg("Question is already closed")]
+    AlreadyClosed,
+    #[msg("Community does not match the question")]
+    WrongCommunity,
+    #[msg("Cancellation reason is too long")]
+    ReasonTooLong,
+}
+
+#[error_code]
+pub enum CloseError {
+    #[msg("Question has not been finalized")]
+    NotFinalized,
+    #[msg("Rent must be returned to the account that paid for it")]
+    WrongRentRecipient,
+    #[msg("Only rejected or removed members can be closed")]
+    MemberStillActive,
+    #[msg("Only the member or a member who can remove members can close this member")]
+    NotAuthorized,
+    #[msg("Member still receives delegated votes")]
+    HoldsDelegations,
+    #[msg("Member must revoke their own delegations first")]
+    HasDelegation,
 }
 
 // --- Event Structs ---
@@ -128,6 +702,7 @@ pub struct CommunityCreated {
     pub community: Pubkey,
     pub admin: Pubkey,
     pub name: String,
+    pub slug: String,
     pub timestamp: i64,
 }
 
@@ -142,8 +717,8 @@ pub struct MemberJoined {
 pub struct MemberApproved {
     pub community: Pubkey,
     pub member_wallet: Pubkey,
-    pub admin: Pubkey,
-    pub status: u8, // 1 = approved, 2 = rejected
+    pub admin: Pubkey, // Signer holding PERMISSION_APPROVE_MEMBERS
+    pub status: MemberStatus, // Approved or rejected
     pub timestamp: i64,
 }
 
@@ -151,7 +726,21 @@ pub struct MemberApproved {
 pub struct MemberRemoved {
     pub community: Pubkey,
     pub member_wallet: Pubkey,
-    pub admin: Pubkey,
+    pub admin: Pubkey, // Signer holding PERMISSION_REMOVE_MEMBERS, or whoever executed the proposal
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MemberLeft {
+    pub community: Pubkey,
+    pub member_wallet: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AccountClosed {
+    pub account: Pubkey,
+    pub rent_recipient: Pubkey,
     pub timestamp: i64,
 }
 
@@ -162,12 +751,37 @@ pub struct CommunityDissolved {
     pub timestamp: i64,
 }
 
+#[event]
+pub struct CommunityPaused {
+    pub community: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CommunityResumed {
+    pub community: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct VotingQuestionCreated {
     pub community: Pubkey,
     pub question: Pubkey,
     pub creator: Pubkey,
+    pub index: u64,
+    pub starts_at: Option<i64>,
     pub deadline: i64,
+    pub action: Option<CommunityAction>,
     pub timestamp: i64,
 }
 
@@ -176,6 +790,9 @@ pub struct VoteCast {
     pub question: Pubkey,
     pub voter: Pubkey,
     pub selected_option: u8,
+    pub selected_options: Vec<u8>,
+    pub allocation: Vec<u16>,
+    pub weight: u64,
     pub timestamp: i64,
 }
 
@@ -187,482 +804,5674 @@ pub struct VotingQuestionClosed {
 }
 
 #[event]
-pub struct MemberRoleChanged {
-    pub community: Pubkey,
-    pub member_wallet: Pubkey,
-    pub new_role: u8, // 0 = member, 1 = admin
-    pub changed_by: Pubkey,
+pub struct VotingQuestionCancelled {
+    pub question: Pubkey,
+    pub result: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub reason: String,
     pub timestamp: i64,
 }
 
-#[error_code]
-pub enum RoleManagementError {
-    #[msg("Only the current admin can change member roles")]
-    NotAdmin,
-    #[msg("Cannot demote self without assigning a new admin")]
-    CannotDemoteSelf,
-    #[msg("Target member is not approved")]
-    NotApprovedMember,
-    #[msg("Target is already the desired role")]
-    AlreadyRole,
+#[event]
+pub struct QuestionFinalized {
+    pub question: Pubkey,
+    pub result: Pubkey,
+    pub winning_options: Vec<u8>,
+    pub total_ballots: u64,
+    pub turnout_bps: u16,
+    pub is_tie: bool,
+    pub outcome: QuestionOutcome,
+    pub unrevealed_commits: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct CreateCommunity<'info> {
-    #[account(
-        init,
-        payer = admin,
-        space = 368, // 8 + 32 + (4+32) + (4+256) + 4 + 8 + 8 + 1 (see review)
-    )]
-    pub community: Account<'info, Community>,
-    #[account(mut)]
-    pub admin: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct VoteChanged {
+    pub question: Pubkey,
+    pub voter: Pubkey,
+    pub previous_options: Vec<u8>,
+    pub selected_options: Vec<u8>,
+    pub allocation: Vec<u16>,
+    pub weight: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct JoinCommunity<'info> {
-    #[account(mut)]
-    pub community: Account<'info, Community>,
-    #[account(
-        init_if_needed,
-        payer = user,
-        space = 96, // 8 + 32 + 32 + 1 + 8 + 1 (see review)
-        seeds = [b"member", community.key().as_ref(), user.key().as_ref()],
-        bump,
-    )]
-    pub member: Account<'info, Member>,
-    #[account(mut)]
-    pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct VoteRetracted {
+    pub question: Pubkey,
+    pub voter: Pubkey,
+    pub weight: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct ApproveMember<'info> {
-    #[account(mut)]
-    pub community: Account<'info, Community>,
-    #[account(mut, has_one = community)]
-    pub member: Account<'info, Member>,
-    #[account(signer)]
-    pub admin: AccountInfo<'info>,
+#[event]
+pub struct VoteCommitted {
+    pub question: Pubkey,
+    pub voter: Pubkey,
+    pub commitment: [u8; 32],
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct CreateVotingQuestion<'info> {
-    #[account(mut)]
-    pub community: Account<'info, Community>,
-    #[account(
-        mut,
-        has_one = community,
-        constraint = member.status == 1 @ VotingQuestionError::NotApprovedMember,
-    )]
-    pub member: Account<'info, Member>,
-    #[account(
-        init,
-        payer = creator,
-        space = 512, // 8 + 32 + 32 + (4+256) + 4 + (4*(4+32)) + 8 + 8 + 1 (see review)
-        seeds = [b"question", community.key().as_ref(), creator.key().as_ref(), &[Clock::get()?.unix_timestamp as u8]],
-        bump,
-    )]
-    pub voting_question: Account<'info, VotingQuestion>,
-    #[account(mut)]
-    pub creator: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct VoteRevealed {
+    pub question: Pubkey,
+    pub voter: Pubkey,
+    pub selected_option: u8,
+    pub weight: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct CastVote<'info> {
-    #[account(mut)]
-    pub voting_question: Account<'info, VotingQuestion>,
-    #[account(
-        mut,
-        has_one = community,
-        constraint = member.status == 1 @ VotingQuestionError::NotApprovedMember,
-    )]
-    pub member: Account<'info, Member>,
-    #[account(mut)]
-    pub voter: Signer<'info>,
-    #[account(
-        init,
-        payer = voter,
-        space = 96, // 8 + 32 + 32 + 1 + 8 (see review)
-        seeds = [b"vote", voting_question.key().as_ref(), voter.key().as_ref()],
-        bump,
-    )]
-    pub vote: Account<'info, Vote>,
-    pub community: Account<'info, Community>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct RunoffRoundCompleted {
+    pub question: Pubkey,
+    pub round: u8,
+    pub vote_counts: Vec<u64>,
+    pub eliminated: Vec<u8>,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct CloseVotingQuestion<'info> {
-    #[account(mut)]
-    pub voting_question: Account<'info, VotingQuestion>,
-    pub closer: Signer<'info>,
+#[event]
+pub struct TokensDeposited {
+    pub community: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_locked: u64,
+    pub timestamp: i64,
 }
 
-#[derive(Accounts)]
-pub struct RemoveMember<'info> {
-    #[account(mut)]
-    pub community: Account<'info, Community>,
-    #[account(mut, has_one = community)]
-    pub member: Account<'info, Member>,
-    #[account(signer)]
-    pub admin: AccountInfo<'info>,
+#[event]
+pub struct TokensWithdrawn {
+    pub community: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_locked: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VoteDelegated {
+    pub community: Pubkey,
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+    pub question: Option<Pubkey>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DelegationRevoked {
+    pub community: Pubkey,
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DelegatedVotesClaimed {
+    pub question: Pubkey,
+    pub delegate: Pubkey,
+    pub delegators: u32,
+    pub weight: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DelegationOverridden {
+    pub question: Pubkey,
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+    pub weight: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MemberRoleChanged {
+    pub community: Pubkey,
+    pub member_wallet: Pubkey,
+    pub new_role: MemberRole,
+    pub permissions: u16,
+    pub changed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MemberPermissionsChanged {
+    pub community: Pubkey,
+    pub member_wallet: Pubkey,
+    pub permissions: u16,
+    pub changed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminAdded {
+    pub community: Pubkey,
+    pub admin: Pubkey,
+    pub added_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminRemoved {
+    pub community: Pubkey,
+    pub admin: Pubkey,
+    pub removed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferProposed {
+    pub community: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub expires_at: Option<i64>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferAccepted {
+    pub community: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminTransferCancelled {
+    pub community: Pubkey,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum AdminTransferError {
+    #[msg("An admin transfer is already pending")]
+    TransferPending,
+    #[msg("No admin transfer is pending")]
+    NoPendingTransfer,
+    #[msg("Only the proposed wallet can accept the transfer")]
+    NotPendingAdmin,
+    #[msg("The admin transfer has expired")]
+    TransferExpired,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Only an admin or the proposed wallet can cancel the transfer")]
+    NotAuthorized,
+    #[msg("Member account does not belong to the outgoing admin")]
+    WrongMemberAccount,
+}
+
+#[event]
+pub struct AdminProposalCreated {
+    pub community: Pubkey,
+    pub proposal: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub action: CommunityAction,
+    pub expires_at: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposalApproved {
+    pub community: Pubkey,
+    pub proposal: Pubkey,
+    pub approver: Pubkey,
+    pub approvals: u8,
+    pub threshold: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposalExecuted {
+    pub community: Pubkey,
+    pub proposal: Pubkey,
+    pub executed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposalCancelled {
+    pub community: Pubkey,
+    pub proposal: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GovernanceActionExecuted {
+    pub community: Pubkey,
+    pub question: Pubkey,
+    pub executed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum GovernanceError {
+    #[msg("Questions carrying an action need a single-choice ballot with two options: approve, reject")]
+    InvalidActionQuestion,
+    #[msg("Question carries no action")]
+    NoAction,
+    #[msg("Action has already been executed")]
+    AlreadyExecuted,
+    #[msg("Question did not pass in favour of the action")]
+    NotApproved,
+    #[msg("Question has a passed action that hasn't been executed")]
+    ActionPending,
+    #[msg("Questions carrying an action need a community quorum rule")]
+    QuorumRequired,
+    #[msg("Questions carrying an action must stay open for at least a day")]
+    ActionVotingPeriodTooShort,
+    #[msg("The window for executing this action has closed")]
+    ExecutionWindowClosed,
+}
+
+#[error_code]
+pub enum MultisigError {
+    #[msg("This community requires an admin proposal for this action")]
+    ProposalRequired,
+    #[msg("Admin has already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Admin proposal has expired")]
+    ProposalExpired,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Admin proposal does not have enough approvals")]
+    ThresholdNotMet,
+    #[msg("Threshold must be between 1 and the number of admins")]
+    InvalidThreshold,
+    #[msg("Removing this admin would leave fewer admins than the threshold")]
+    BelowThreshold,
+    #[msg("This action needs the targeted member's account")]
+    MemberAccountRequired,
+    #[msg("Member account does not match the proposal")]
+    WrongMember,
+    #[msg("Only an admin can cancel an unexpired proposal")]
+    NotAuthorized,
+}
+
+#[error_code]
+pub enum AdminSetError {
+    #[msg("Wallet is already a community admin")]
+    AlreadyAdmin,
+    #[msg("Wallet is not a community admin")]
+    NotAnAdmin,
+    #[msg("Community already has the maximum number of admins")]
+    TooManyAdmins,
+    #[msg("Cannot remove the last community admin")]
+    LastAdmin,
+    #[msg("Admins are managed with add_admin and remove_admin")]
+    UseAdminInstructions,
+}
+
+#[error_code]
+pub enum RoleManagementError {
+    #[msg("Only the current admin can change member roles")]
+    NotAdmin,
+    #[msg("Cannot demote self without assigning a new admin")]
+    CannotDemoteSelf,
+    #[msg("Target member is not approved")]
+    NotApprovedMember,
+    #[msg("Target is already the desired role")]
+    AlreadyRole,
 }
 
 #[derive(Accounts)]
-pub struct DissolveCommunity<'info> {
+#[instruction(name: String)]
+pub struct CreateCommunity<'info> {
+    // One community per normalized name: a second `init` on the same slug fails
+    #[account(
+        init,
+        payer = admin,
+        space = COMMUNITY_SPACE,
+        seeds = [b"community", community_seed(&community_slug(&name)).as_ref()],
+        bump,
+    )]
+    pub community: Account<'info, Community>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinCommunity<'info> {
     #[account(mut)]
     pub community: Account<'info, Community>,
-    #[account(signer)]
-    pub admin: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = MEMBER_SPACE,
+        seeds = [b"member", community.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateCommunityConfig<'info> {
+pub struct ApproveMember<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community)]
+    pub member: Account<'info, Member>,
+    // Needs PERMISSION_APPROVE_MEMBERS
+    pub authority: Signer<'info>,
+    // The signer's Member account; not needed when the signer is a community admin
+    pub authority_member: Option<Account<'info, Member>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateVotingQuestion<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(
+        mut,
+        has_one = community,
+        constraint = member.wallet == creator.key() @ VotingQuestionError::WrongMember,
+        constraint = member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(
+        init,
+        payer = creator,
+        space = QUESTION_SPACE,
+        seeds = [b"question", community.key().as_ref(), &community.question_count.to_le_bytes()],
+        bump,
+    )]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CastVote<'info> {
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
     #[account(
         mut,
-        has_one = admin @ AdminActionError::NotAdmin
+        has_one = community,
+        constraint = member.wallet == voter.key() @ VotingQuestionError::WrongMember,
+        constraint = member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    // May already hold a marker left by a delegate, which a direct vote overrides
+    #[account(
+        init_if_needed,
+        payer = voter,
+        space = VOTE_SPACE,
+        seeds = [b"vote", voting_question.key().as_ref(), voter.key().as_ref()],
+        bump,
     )]
+    pub vote: Account<'info, Vote>,
+    #[account(constraint = community.key() == voting_question.community @ VotingQuestionError::WrongCommunity)]
     pub community: Account<'info, Community>,
-    #[account(signer)]
-    pub admin: AccountInfo<'info>,
+    // Required when the question is weighted by token balance
+    pub voter_token_account: Option<Account<'info, TokenAccount>>,
+    // Required when the question is weighted by locked tokens
+    #[account(mut)]
+    pub voter_escrow: Option<Account<'info, VoterEscrow>>,
+    // Required when overriding a delegate who already counted this voter's weight
+    #[account(mut)]
+    pub delegate_vote: Option<Account<'info, Vote>>,
+    /// CHECK: only receives lamports; the overridden delegate, refunded the marker's rent
+    #[account(mut)]
+    pub delegate_wallet: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+    // Remaining accounts, per delegator whose weight this ballot also counts: [delegation,
+    // delegator member, delegator vote PDA, delegator's delegation PDA for this question],
+    // followed by the delegator's escrow when the question is weighted by locked tokens
+}
+
+#[derive(Accounts)]
+pub struct ChangeVote<'info> {
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(
+        mut,
+        seeds = [b"vote", voting_question.key().as_ref(), voter.key().as_ref()],
+        bump,
+    )]
+    pub vote: Account<'info, Vote>,
+    #[account(
+        has_one = community,
+        constraint = member.wallet == voter.key() @ DelegationError::WrongMember,
+        constraint = member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(constraint = community.key() == voting_question.community @ VoteChangeError::WrongCommunity)]
+    pub community: Account<'info, Community>,
+    pub voter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RetractVote<'info> {
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    // Closing refunds the rent; the member may cast a fresh ballot afterwards
+    #[account(
+        mut,
+        close = voter,
+        seeds = [b"vote", voting_question.key().as_ref(), voter.key().as_ref()],
+        bump,
+    )]
+    pub vote: Account<'info, Vote>,
+    #[account(constraint = community.key() == voting_question.community @ VoteChangeError::WrongCommunity)]
+    pub community: Account<'info, Community>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevealVote<'info> {
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(
+        mut,
+        seeds = [b"vote", voting_question.key().as_ref(), voter.key().as_ref()],
+        bump,
+    )]
+    pub vote: Account<'info, Vote>,
+    pub voter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVotingQuestion<'info> {
+    #[account(mut, has_one = community @ QuestionCloseError::WrongCommunity)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    pub community: Account<'info, Community>,
+    // Anyone may close once the deadline has passed
+    pub closer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelVotingQuestion<'info> {
+    #[account(mut, has_one = community @ QuestionCloseError::WrongCommunity)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    pub community: Account<'info, Community>,
+    // A cancelled question gets a result too, so its votes and rent can be reclaimed
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + QuestionResult::INIT_SPACE,
+        seeds = [b"result", voting_question.key().as_ref()],
+        bump,
+    )]
+    pub result: Account<'info, QuestionResult>,
+    // The question creator, or a signer with PERMISSION_CLOSE_QUESTIONS
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    // The signer's Member account; not needed when the signer is a community admin
+    pub authority_member: Option<Account<'info, Member>>,
+    pub system_program: Program<'info, System>,
 }
 
-#[derive(Accounts)]
-pub struct ChangeMemberRole<'info> {
-    #[account(mut, has_one = admin)]
-    pub community: Account<'info, Community>,
-    #[account(mut, has_one = community)]
-    pub member: Account<'info, Member>,
-    #[account(signer)]
-    pub admin: AccountInfo<'info>,
-}
+#[derive(Accounts)]
+pub struct FinalizeQuestion<'info> {
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + QuestionResult::INIT_SPACE,
+        seeds = [b"result", voting_question.key().as_ref()],
+        bump,
+    )]
+    pub result: Account<'info, QuestionResult>,
+    // Required for ranked-choice questions; closed once its result is recorded
+    #[account(mut)]
+    pub runoff: Option<Account<'info, RunoffTally>>,
+    /// CHECK: must match runoff.payer; only receives the runoff account's rent
+    #[account(mut)]
+    pub runoff_payer: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BeginRunoff<'info> {
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + RunoffTally::INIT_SPACE,
+        seeds = [b"runoff", voting_question.key().as_ref()],
+        bump,
+    )]
+    pub runoff: Account<'info, RunoffTally>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessRunoffBallots<'info> {
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(
+        mut,
+        seeds = [b"runoff", voting_question.key().as_ref()],
+        bump,
+    )]
+    pub runoff: Account<'info, RunoffTally>,
+    // Vote accounts to count are passed as writable remaining accounts
+}
+
+#[derive(Accounts)]
+pub struct DepositTokens<'info> {
+    pub community: Account<'info, Community>,
+    #[account(
+        has_one = community,
+        constraint = member.wallet == owner.key() @ EscrowError::WrongEscrow,
+        constraint = member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + VoterEscrow::INIT_SPACE,
+        seeds = [b"escrow", community.key().as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub escrow: Account<'info, VoterEscrow>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        token::mint = mint,
+        token::authority = vault,
+        seeds = [b"vault", community.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint, token::authority = owner)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTokens<'info> {
+    pub community: Account<'info, Community>,
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = mint,
+        seeds = [b"escrow", community.key().as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub escrow: Account<'info, VoterEscrow>,
+    pub mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"vault", community.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(question: Option<Pubkey>)]
+pub struct DelegateVote<'info> {
+    pub community: Account<'info, Community>,
+    #[account(
+        mut,
+        has_one = community,
+        constraint = delegator_member.wallet == delegator.key() @ DelegationError::WrongMember,
+        constraint = delegator_member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub delegator_member: Account<'info, Member>,
+    #[account(
+        mut,
+        constraint = delegate_member.community == community.key() @ DelegationError::WrongMember,
+        constraint = delegate_member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub delegate_member: Account<'info, Member>,
+    #[account(
+        init,
+        payer = delegator,
+        space = 8 + Delegation::INIT_SPACE,
+        // Scoped by question, or by the default key for a global delegation
+        seeds = [
+            b"delegation",
+            community.key().as_ref(),
+            delegator.key().as_ref(),
+            question.unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeDelegation<'info> {
+    #[account(
+        mut,
+        has_one = delegator,
+        close = delegator,
+        seeds = [
+            b"delegation",
+            delegation.community.as_ref(),
+            delegator.key().as_ref(),
+            delegation.question.unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        mut,
+        constraint = delegator_member.community == delegation.community @ DelegationError::WrongMember,
+        constraint = delegator_member.wallet == delegator.key() @ DelegationError::WrongMember,
+    )]
+    pub delegator_member: Account<'info, Member>,
+    #[account(
+        mut,
+        constraint = delegate_member.community == delegation.community @ DelegationError::WrongMember,
+        constraint = delegate_member.wallet == delegation.delegate @ DelegationError::WrongMember,
+    )]
+    pub delegate_member: Account<'info, Member>,
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+}
+
+// cast_vote counts the delegations passed along with the ballot; this counts the ones
+// made (or passed) only after the delegate had already voted
+#[derive(Accounts)]
+pub struct ClaimDelegatedVotes<'info> {
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(
+        mut,
+        seeds = [b"vote", voting_question.key().as_ref(), voter.key().as_ref()],
+        bump,
+        constraint = vote.delegated_to.is_none() @ DelegationError::NotDirectVote,
+    )]
+    pub vote: Account<'info, Vote>,
+    #[account(
+        constraint = member.community == voting_question.community @ DelegationError::WrongMember,
+        constraint = member.wallet == voter.key() @ DelegationError::WrongMember,
+        constraint = member.status == MemberStatus::Approved @ VotingQuestionError::NotApprovedMember,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    // Remaining accounts: delegation groups, as for cast_vote
+}
+
+#[derive(Accounts)]
+pub struct RemoveMember<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community)]
+    pub member: Account<'info, Member>,
+    // Needs PERMISSION_REMOVE_MEMBERS
+    pub authority: Signer<'info>,
+    // The signer's Member account; not needed when the signer is a community admin
+    pub authority_member: Option<Account<'info, Member>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVote<'info> {
+    // The result account proves finalization, even once the question itself is closed
+    #[account(seeds = [b"result", vote.question.as_ref()], bump)]
+    pub result: Account<'info, QuestionResult>,
+    #[account(
+        mut,
+        close = rent_recipient,
+        seeds = [b"vote", vote.question.as_ref(), vote.voter.as_ref()],
+        bump,
+    )]
+    pub vote: Account<'info, Vote>,
+    /// CHECK: only receives lamports; must be whoever paid for the vote account
+    #[account(
+        mut,
+        constraint = rent_recipient.key() == vote_rent_payer(&vote) @ CloseError::WrongRentRecipient,
+    )]
+    pub rent_recipient: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseFinalizedQuestion<'info> {
+    #[account(
+        mut,
+        close = creator,
+        has_one = creator @ CloseError::WrongRentRecipient,
+        constraint = voting_question.is_finalized @ CloseError::NotFinalized,
+    )]
+    pub voting_question: Account<'info, VotingQuestion>,
+    // Results must be persisted before the question goes away
+    #[account(seeds = [b"result", voting_question.key().as_ref()], bump)]
+    pub result: Account<'info, QuestionResult>,
+    /// CHECK: only receives lamports; checked against voting_question.creator
+    #[account(mut)]
+    pub creator: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveCommunity<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"member", community.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub member: Account<'info, Member>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseMember<'info> {
+    pub community: Account<'info, Community>,
+    #[account(
+        mut,
+        has_one = community,
+        close = wallet,
+        seeds = [b"member", community.key().as_ref(), wallet.key().as_ref()],
+        bump,
+    )]
+    pub member: Account<'info, Member>,
+    /// CHECK: only receives lamports; the member PDA seeds tie it to the member
+    #[account(mut)]
+    pub wallet: UncheckedAccount<'info>,
+    // The member themselves, or a signer with PERMISSION_REMOVE_MEMBERS
+    pub closer: Signer<'info>,
+    // The closer's Member account; not needed when the closer is a community admin
+    pub closer_member: Option<Account<'info, Member>>,
+}
+
+#[derive(Accounts)]
+pub struct DissolveCommunity<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCommunityStatus<'info> {
+    #[account(
+        mut,
+        constraint = is_admin(&community, admin.key) @ AdminActionError::NotAdmin
+    )]
+    pub community: Account<'info, Community>,
+    #[account(signer)]
+    pub admin: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AddAdmin<'info> {
+    #[account(
+        mut,
+        constraint = is_admin(&community, admin.key) @ AdminActionError::NotAdmin
+    )]
+    pub community: Account<'info, Community>,
+    // The new admin must be an approved member
+    #[account(mut, has_one = community)]
+    pub member: Account<'info, Member>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct RemoveAdmin<'info> {
+    #[account(
+        mut,
+        constraint = is_admin(&community, admin.key) @ AdminActionError::NotAdmin
+    )]
+    pub community: Account<'info, Community>,
+    /// CHECK: the removed admin's Member PDA, demoted if it exists. Admins such as the
+    /// creator may never have joined, so it can be empty.
+    #[account(
+        mut,
+        seeds = [b"member", community.key().as_ref(), wallet.as_ref()],
+        bump,
+    )]
+    pub member: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+}
+
+// Shared by propose_admin_transfer and cancel_admin_transfer
+#[derive(Accounts)]
+pub struct ManageAdminTransfer<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdminTransfer<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    /// CHECK: the outgoing admin's Member PDA (checked in the handler), demoted if it exists
+    #[account(mut)]
+    pub previous_member: UncheckedAccount<'info>,
+    /// CHECK: the new admin's Member PDA, promoted if it exists
+    #[account(
+        mut,
+        seeds = [b"member", community.key().as_ref(), new_admin.key().as_ref()],
+        bump,
+    )]
+    pub new_member: UncheckedAccount<'info>,
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAdminProposal<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + AdminProposal::INIT_SPACE,
+        seeds = [b"admin_proposal", community.key().as_ref(), &community.admin_proposal_count.to_le_bytes()],
+        bump,
+    )]
+    pub proposal: Account<'info, AdminProposal>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveAdminProposal<'info> {
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community)]
+    pub proposal: Account<'info, AdminProposal>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAdminProposal<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community, has_one = proposer, close = proposer)]
+    pub proposal: Account<'info, AdminProposal>,
+    // Receives the proposal's rent
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+    // The member an action targets, if any
+    #[account(mut, has_one = community)]
+    pub member: Option<Account<'info, Member>>,
+    /// CHECK: for RemoveAdmin, the removed admin's Member PDA (checked against
+    /// member_address in the handler), demoted if it exists
+    #[account(mut)]
+    pub admin_member: Option<UncheckedAccount<'info>>,
+    // Anyone may execute an approved proposal
+    pub executor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAdminProposal<'info> {
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community, has_one = proposer, close = proposer)]
+    pub proposal: Account<'info, AdminProposal>,
+    // Receives the proposal's rent
+    #[account(mut)]
+    pub proposer: SystemAccount<'info>,
+    // Any admin, or anyone once the proposal has expired
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteQuestionAction<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(seeds = [b"result", voting_question.key().as_ref()], bump)]
+    pub result: Account<'info, QuestionResult>,
+    // The member an action targets, if any
+    #[account(mut, has_one = community)]
+    pub member: Option<Account<'info, Member>>,
+    /// CHECK: for RemoveAdmin and ReplaceAdmin, the unseated admin's Member PDA
+    /// (checked against member_address in the handler), demoted if it exists
+    #[account(mut)]
+    pub admin_member: Option<UncheckedAccount<'info>>,
+    // Anyone may execute a passed action
+    pub executor: Signer<'info>,
+}
+
+// Shared by migrate_community and migrate_member
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// CHECK: may still use an old layout that `Account<T>` can't load;
+    /// the discriminator and version are checked in the handler
+    #[account(mut, owner = crate::ID)]
+    pub account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVotingQuestion<'info> {
+    /// CHECK: may still use an old layout that `Account<T>` can't load;
+    /// the discriminator and version are checked in the handler
+    #[account(mut, owner = crate::ID)]
+    pub account: UncheckedAccount<'info>,
+    // Already migrated; supplies the question's eligible voters
+    pub community: Account<'info, Community>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVote<'info> {
+    /// CHECK: may still use an old layout that `Account<T>` can't load;
+    /// the discriminator and version are checked in the handler
+    #[account(mut, owner = crate::ID)]
+    pub account: UncheckedAccount<'info>,
+    // Already migrated; the vote is counted into its tally
+    #[account(mut)]
+    pub voting_question: Account<'info, VotingQuestion>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCommunityConfig<'info> {
+    #[account(mut)]
+    pub community: Account<'info, Community>,
+    // Needs PERMISSION_EDIT_CONFIG
+    pub authority: Signer<'info>,
+    // The signer's Member account; not needed when the signer is a community admin
+    pub authority_member: Option<Account<'info, Member>>,
+}
+
+// Shared by change_member_role and set_member_permissions
+#[derive(Accounts)]
+pub struct ChangeMemberRole<'info> {
+    pub community: Account<'info, Community>,
+    #[account(mut, has_one = community)]
+    pub member: Account<'info, Member>,
+    // Needs PERMISSION_MANAGE_ROLES, and can only grant permissions it holds itself
+    pub authority: Signer<'info>,
+    // The signer's Member account; not needed when the signer is a community admin
+    pub authority_member: Option<Account<'info, Member>>,
+}
+
+// Schema version of a program account: 0 for accounts written before versioning, which
+// were all allocated with one fixed size, otherwise the byte after the discriminator
+fn stored_version(data: &[u8], discriminator: [u8; 8], unversioned_size: usize) -> Result<u8> {
+    if data.len() < 9 || data[..8] != discriminator {
+        return err!(MigrationError::WrongAccountType);
+    }
+    if data.len() == unversioned_size {
+        return Ok(0);
+    }
+    Ok(data[8])
+}
+
+// Upgrade a community stored at `version` (`body` is the data after the discriminator)
+fn migrated_community(version: u8, body: &[u8]) -> Result<Community> {
+    match version {
+        0 => {
+            let old = CommunityV0::deserialize(&mut &body[..])?;
+            let mut community = Community {
+                version: COMMUNITY_VERSION,
+                admins: vec![old.admin], // The single admin becomes the whole admin set
+                name: old.name,
+                description: old.description,
+                member_count: old.member_count,
+                created_at: old.created_at,
+                // Rules added since keep their defaults, which match the old behaviour
+                config: CommunityConfig {
+                    voting_period: old.config.voting_period,
+                    max_options: old.config.max_options,
+                    ..CommunityConfig::default()
+                },
+                // Old questions were keypair accounts outside the sequence
+                question_count: 0,
+                // Keypair communities don't live at a name PDA, so they reserve no slug
+                slug: String::new(),
+                status: CommunityStatus::Active,
+                pending_admin: None,
+                // Admins keep acting alone until a proposal raises the threshold
+                admin_threshold: 1,
+                admin_proposal_count: 0,
+            };
+            replace_dissolved_sentinel(&mut community);
+            Ok(community)
+        }
+        COMMUNITY_VERSION => err!(MigrationError::AlreadyCurrent),
+        _ => err!(MigrationError::UnknownVersion),
+    }
+}
+
+// Upgrade a member stored at `version`
+fn migrated_member(version: u8, body: &[u8]) -> Result<Member> {
+    match version {
+        0 => {
+            let old = MemberV0::deserialize(&mut &body[..])?;
+            Ok(Member {
+                version: MEMBER_VERSION,
+                community: old.community,
+                wallet: old.wallet,
+                role: old.role,
+                joined_at: old.joined_at,
+                status: old.status,
+                delegations_received: 0,
+                delegations_given: 0,
+                permissions: role_permissions(old.role),
+            })
+        }
+        MEMBER_VERSION => err!(MigrationError::AlreadyCurrent),
+        _ => err!(MigrationError::UnknownVersion),
+    }
+}
+
+// Upgrade a question stored at `version`. Old questions kept no tally: it starts empty
+// and each vote adds itself back as it is migrated (see migrate_vote).
+fn migrated_voting_question(version: u8, body: &[u8], eligible_voters: u32) -> Result<VotingQuestion> {
+    match version {
+        0 => {
+            let old = VotingQuestionV0::deserialize(&mut &body[..])?;
+            Ok(VotingQuestion {
+                version: QUESTION_VERSION,
+                community: old.community,
+                creator: old.creator,
+                question: old.question,
+                vote_counts: vec![0; old.options.len()],
+                options: old.options,
+                deadline: old.deadline,
+                created_at: old.created_at,
+                is_active: old.is_active,
+                total_ballots: 0,
+                // No snapshot was taken, so use the community's membership at migration
+                eligible_voters,
+                is_finalized: false,
+                quorum: QuorumRule::None,
+                pass_threshold: PassThreshold::SimpleMajority,
+                ballot_type: BallotType::SingleChoice,
+                vote_weighting: VoteWeighting::OnePerMember,
+                total_weight: 0,
+                reveal_deadline: None,
+                total_commits: 0,
+                index: 0,
+                // Old questions opened for voting at creation
+                starts_at: None,
+                action: None,
+                action_executed: false,
+            })
+        }
+        QUESTION_VERSION => err!(MigrationError::AlreadyCurrent),
+        _ => err!(MigrationError::UnknownVersion),
+    }
+}
+
+// Upgrade a vote stored at `version`
+fn migrated_vote(version: u8, body: &[u8]) -> Result<Vote> {
+    match version {
+        0 => {
+            let old = VoteV0::deserialize(&mut &body[..])?;
+            Ok(Vote {
+                version: VOTE_VERSION,
+                question: old.question,
+                voter: old.voter,
+                selected_option: old.selected_option,
+                voted_at: old.voted_at,
+                selected_options: vec![old.selected_option],
+                tallied_round: 0,
+                weight: 1,
+                allocation: Vec::new(),
+                delegated_to: None,
+                delegated_weight: 0,
+                commitment: None,
+                revealed: false,
+            })
+        }
+        VOTE_VERSION => err!(MigrationError::AlreadyCurrent),
+        _ => err!(MigrationError::UnknownVersion),
+    }
+}
+
+// Add a migrated vote back into its question's tally. A finalized result is immutable,
+// so votes migrated after finalization are upgraded without being counted.
+fn recount_migrated_vote(voting_question: &mut VotingQuestion, vote: &Vote) -> Result<()> {
+    if voting_question.is_finalized {
+        return Ok(());
+    }
+    let contributions =
+        ballot_contributions(voting_question.ballot_type, &vote.selected_options, &vote.allocation, vote.weight)?;
+    adjust_tally(voting_question, contributions, 1, vote.weight, true)
+}
+
+// Write an account over `info` in its current layout, resizing the allocation to exactly
+// `space` (and topping up rent from `payer` when it grows)
+fn rewrite_account<'info, T: AccountSerialize>(
+    info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+    space: usize,
+    account: &T,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    if rent > info.lamports() {
+        system_program::transfer(
+            CpiContext::new(
+                system.clone(),
+                system_program::Transfer { from: payer.clone(), to: info.clone() },
+            ),
+            rent - info.lamports(),
+        )?;
+    }
+    // An exact size keeps a migrated account from being mistaken for an unversioned one
+    if info.data_len() != space {
+        info.realloc(space, true)?;
+    }
+    account.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])
+}
+
+// Anyone may close a question once its deadline has passed
+fn ensure_question_closable(voting_question: &VotingQuestion, community: &Community, now: i64) -> Result<()> {
+    if community.status == CommunityStatus::Dissolved {
+        return err!(EdgeCaseError::CommunityDissolved);
+    }
+    if !voting_question.is_active {
+        return err!(QuestionCloseError::AlreadyClosed);
+    }
+    if now <= voting_question.deadline {
+        return err!(QuestionCloseError::VotingStillOpen);
+    }
+    Ok(())
+}
+
+// Whether a pending transfer can no longer be accepted
+fn transfer_expired(transfer: &AdminTransfer, now: i64) -> bool {
+    matches!(transfer.expires_at, Some(expires_at) if now > expires_at)
+}
+
+// Record `from`'s offer of their admin seat to `to`. An expired offer no longer
+// blocks a new one.
+fn propose_transfer(community: &mut Community, from: Pubkey, to: Pubkey, expires_at: Option<i64>, now: i64) -> Result<()> {
+    if !is_admin(community, &from) {
+        return err!(AdminActionError::NotAdmin);
+    }
+    if community.status == CommunityStatus::Dissolved {
+        return err!(AdminActionError::CommunityDissolved);
+    }
+    if matches!(&community.pending_admin, Some(transfer) if !transfer_expired(transfer, now)) {
+        return err!(AdminTransferError::TransferPending);
+    }
+    if is_admin(community, &to) {
+        return err!(AdminSetError::AlreadyAdmin);
+    }
+    if matches!(expires_at, Some(expires_at) if expires_at <= now) {
+        return err!(AdminTransferError::InvalidExpiry);
+    }
+    community.pending_admin = Some(AdminTransfer {
+        from,
+        to,
+        proposed_at: now,
+        expires_at,
+    });
+    Ok(())
+}
+
+// Hand the proposer's seat to `new_admin` and return the transfer it completes
+fn accept_transfer(community: &mut Community, new_admin: Pubkey, now: i64) -> Result<AdminTransfer> {
+    let Some(transfer) = community.pending_admin.clone() else {
+        return err!(AdminTransferError::NoPendingTransfer);
+    };
+    if transfer.to != new_admin {
+        return err!(AdminTransferError::NotPendingAdmin);
+    }
+    if transfer_expired(&transfer, now) {
+        return err!(AdminTransferError::TransferExpired);
+    }
+    // remove_admin clears a transfer whose proposer loses their seat, so `from` is still an admin
+    let Some(position) = community.admins.iter().position(|admin| *admin == transfer.from) else {
+        return err!(AdminSetError::NotAnAdmin);
+    };
+    if is_admin(community, &new_admin) {
+        return err!(AdminSetError::AlreadyAdmin);
+    }
+    community.admins[position] = new_admin;
+    community.pending_admin = None;
+    Ok(transfer)
+}
+
+// Once a community requires several admin approvals, admins go through AdminProposals
+// for the actions those cover. Permissions granted to members are unaffected.
+fn ensure_admin_may_act_alone(community: &Community, signer: &Pubkey) -> Result<()> {
+    if community.admin_threshold > 1 && is_admin(community, signer) {
+        return err!(MultisigError::ProposalRequired);
+    }
+    Ok(())
+}
+
+// Above a threshold of 1, admin-set changes and permission grants go through AdminProposals
+// for everyone: otherwise one admin could seat a second wallet, or empower one, and act twice
+fn ensure_no_admin_threshold(community: &Community) -> Result<()> {
+    if community.admin_threshold > 1 {
+        return err!(MultisigError::ProposalRequired);
+    }
+    Ok(())
+}
+
+// Take the next position in the community's admin proposal sequence
+fn next_admin_proposal_index(community: &mut Community) -> Result<u64> {
+    let index = community.admin_proposal_count;
+    community.admin_proposal_count = index.checked_add(1).ok_or(VotingError::CounterOverflow)?;
+    Ok(index)
+}
+
+// Admins who approved and still hold their seat
+fn counted_approvals(community: &Community, proposal: &AdminProposal) -> usize {
+    proposal.approvals.iter().filter(|approver| is_admin(community, approver)).count()
+}
+
+// Add `admin`'s approval to an unexpired proposal
+fn record_approval(community: &Community, proposal: &mut AdminProposal, admin: Pubkey, now: i64) -> Result<()> {
+    if !is_admin(community, &admin) {
+        return err!(AdminActionError::NotAdmin);
+    }
+    if now > proposal.expires_at {
+        return err!(MultisigError::ProposalExpired);
+    }
+    if proposal.approvals.contains(&admin) {
+        return err!(MultisigError::AlreadyApproved);
+    }
+    // Drop approvals from admins who have since lost their seat, keeping the list within MAX_ADMINS
+    proposal.approvals.retain(|approver| is_admin(community, approver));
+    proposal.approvals.push(admin);
+    Ok(())
+}
+
+// A proposal executes before it expires, once `admin_threshold` current admins approved it
+fn ensure_proposal_executable(community: &Community, proposal: &AdminProposal, now: i64) -> Result<()> {
+    if now > proposal.expires_at {
+        return err!(MultisigError::ProposalExpired);
+    }
+    if counted_approvals(community, proposal) < community.admin_threshold as usize {
+        return err!(MultisigError::ThresholdNotMet);
+    }
+    Ok(())
+}
+
+fn validate_community_action(community: &Community, action: &CommunityAction) -> Result<()> {
+    match action {
+        CommunityAction::UpdateConfig { config } => validate_config(config),
+        CommunityAction::SetAdminThreshold { threshold } => {
+            if *threshold == 0 || *threshold as usize > community.admins.len() {
+                return err!(MultisigError::InvalidThreshold);
+            }
+            Ok(())
+        }
+        CommunityAction::SetMemberPermissions { permissions, .. } => {
+            if permissions & !ALL_PERMISSIONS != 0 {
+                return err!(PermissionError::InvalidPermissions);
+            }
+            Ok(())
+        }
+        CommunityAction::ChangeMemberRole { role, .. } => {
+            if *role == MemberRole::Admin {
+                return err!(AdminSetError::UseAdminInstructions);
+            }
+            Ok(())
+        }
+        CommunityAction::RemoveMember { .. }
+        | CommunityAction::DissolveCommunity
+        | CommunityAction::AddAdmin { .. }
+        | CommunityAction::RemoveAdmin { .. }
+        | CommunityAction::ReplaceAdmin { .. } => Ok(()),
+    }
+}
+
+// Did the question pass with option 0, approving its action?
+fn action_approved(result: &QuestionResult) -> bool {
+    result.outcome == QuestionOutcome::Passed && !result.is_tie && result.winning_options == [0]
+}
+
+// A question carrying an action is a binding approve/reject vote: option 0 approves and
+// option 1 rejects, and it needs real turnout and time for members to notice it
+fn validate_action_question(
+    community: &Community,
+    action: &CommunityAction,
+    ballot_type: BallotType,
+    option_count: usize,
+    opens_at: i64,
+    deadline: i64,
+) -> Result<()> {
+    if ballot_type != BallotType::SingleChoice || option_count != 2 {
+        return err!(GovernanceError::InvalidActionQuestion);
+    }
+    if community.config.quorum == QuorumRule::None {
+        return err!(GovernanceError::QuorumRequired);
+    }
+    if deadline - opens_at < MIN_ACTION_VOTING_PERIOD {
+        return err!(GovernanceError::ActionVotingPeriodTooShort);
+    }
+    validate_community_action(community, action)
+}
+
+// Last moment a passed action may be executed: ACTION_EXECUTION_WINDOW after voting ends
+fn action_execution_deadline(voting_question: &VotingQuestion) -> i64 {
+    let voting_end = voting_question.reveal_deadline.unwrap_or(voting_question.deadline);
+    voting_end.saturating_add(ACTION_EXECUTION_WINDOW)
+}
+
+// The action a question approved, if it may still be executed
+fn executable_action(voting_question: &VotingQuestion, result: &QuestionResult, now: i64) -> Result<CommunityAction> {
+    let Some(action) = voting_question.action.clone() else {
+        return err!(GovernanceError::NoAction);
+    };
+    if voting_question.action_executed {
+        return err!(GovernanceError::AlreadyExecuted);
+    }
+    if !action_approved(result) {
+        return err!(GovernanceError::NotApproved);
+    }
+    if now > action_execution_deadline(voting_question) {
+        return err!(GovernanceError::ExecutionWindowClosed);
+    }
+    Ok(action)
+}
+
+// A finalized question can be closed unless it still has a passed action awaiting execution
+fn ensure_action_settled(voting_question: &VotingQuestion, result: &QuestionResult, now: i64) -> Result<()> {
+    if voting_question.action.is_some()
+        && !voting_question.action_executed
+        && action_approved(result)
+        && now <= action_execution_deadline(voting_question)
+    {
+        return err!(GovernanceError::ActionPending);
+    }
+    Ok(())
+}
+
+// The Member account of the admin an action unseats, which must come along so it can be demoted
+fn unseated_admin_member<'info>(
+    action: &CommunityAction,
+    admin_member: Option<&UncheckedAccount<'info>>,
+    community: &Pubkey,
+) -> Result<Option<AccountInfo<'info>>> {
+    let wallet = match action {
+        CommunityAction::RemoveAdmin { wallet } => wallet,
+        CommunityAction::ReplaceAdmin { old_admin, .. } => old_admin,
+        _ => return Ok(None),
+    };
+    let Some(info) = admin_member else {
+        return err!(MultisigError::MemberAccountRequired);
+    };
+    if info.key() != member_address(community, wallet) {
+        return err!(MultisigError::WrongMember);
+    }
+    Ok(Some(info.to_account_info()))
+}
+
+// The member account an action targets, checked against the wallet it names
+fn target_member<'a>(member: Option<&'a mut Member>, wallet: &Pubkey, community: &Pubkey) -> Result<&'a mut Member> {
+    let Some(member) = member else {
+        return err!(MultisigError::MemberAccountRequired);
+    };
+    if member.wallet != *wallet || member.community != *community {
+        return err!(MultisigError::WrongMember);
+    }
+    Ok(member)
+}
+
+// Apply an approved action. `member` must be the member the action targets, if any;
+// for RemoveAdmin and ReplaceAdmin the caller demotes the removed admin's Member account.
+fn execute_community_action(
+    community: &mut Community,
+    community_key: Pubkey,
+    member: Option<&mut Member>,
+    action: &CommunityAction,
+    executed_by: Pubkey,
+    now: i64,
+) -> Result<()> {
+    if community.status == CommunityStatus::Dissolved {
+        return err!(AdminActionError::CommunityDissolved);
+    }
+    validate_community_action(community, action)?;
+    match action {
+        CommunityAction::UpdateConfig { config } => {
+            community.config = config.clone();
+            msg!("Community config updated by {}", executed_by);
+            Ok(())
+        }
+        CommunityAction::RemoveMember { wallet } => {
+            let member = target_member(member, wallet, &community_key)?;
+            apply_remove_member(community, community_key, member, executed_by, now)
+        }
+        CommunityAction::ReplaceAdmin { old_admin, new_admin } => {
+            let Some(position) = community.admins.iter().position(|admin| admin == old_admin) else {
+                return err!(AdminSetError::NotAnAdmin);
+            };
+            if is_admin(community, new_admin) {
+                return err!(AdminSetError::AlreadyAdmin);
+            }
+            community.admins[position] = *new_admin;
+            if matches!(&community.pending_admin, Some(transfer) if transfer.from == *old_admin) {
+                community.pending_admin = None;
+            }
+            emit!(AdminRemoved {
+                community: community_key,
+                admin: *old_admin,
+                removed_by: executed_by,
+                timestamp: now,
+            });
+            emit!(AdminAdded {
+                community: community_key,
+                admin: *new_admin,
+                added_by: executed_by,
+                timestamp: now,
+            });
+            Ok(())
+        }
+        CommunityAction::DissolveCommunity => apply_dissolve(community, community_key, executed_by, now),
+        CommunityAction::SetAdminThreshold { threshold } => {
+            community.admin_threshold = *threshold;
+            Ok(())
+        }
+        CommunityAction::AddAdmin { wallet } => {
+            let member = target_member(member, wallet, &community_key)?;
+            apply_add_admin(community, community_key, member, executed_by, now)
+        }
+        CommunityAction::RemoveAdmin { wallet } => apply_remove_admin(community, community_key, *wallet, executed_by, now),
+        CommunityAction::ChangeMemberRole { wallet, role } => {
+            let member = target_member(member, wallet, &community_key)?;
+            apply_role_change(community_key, member, *role, executed_by, now)
+        }
+        CommunityAction::SetMemberPermissions { wallet, permissions } => {
+            let member = target_member(member, wallet, &community_key)?;
+            apply_permissions_change(community_key, member, *permissions, executed_by, now)
+        }
+    }
+}
+
+// Shared by add_admin and executed AddAdmin actions
+fn apply_add_admin(community: &mut Community, community_key: Pubkey, member: &mut Member, added_by: Pubkey, now: i64) -> Result<()> {
+    add_to_admins(community, member)?;
+    emit!(AdminAdded {
+        community: community_key,
+        admin: member.wallet,
+        added_by,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Shared by remove_admin and executed RemoveAdmin actions; callers demote the Member account
+fn apply_remove_admin(community: &mut Community, community_key: Pubkey, wallet: Pubkey, removed_by: Pubkey, now: i64) -> Result<()> {
+    remove_from_admins(community, wallet)?;
+    emit!(AdminRemoved {
+        community: community_key,
+        admin: wallet,
+        removed_by,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Shared by change_member_role and executed ChangeMemberRole actions
+fn apply_role_change(community: Pubkey, member: &mut Member, new_role: MemberRole, changed_by: Pubkey, now: i64) -> Result<()> {
+    // Only approved members can be promoted/demoted
+    if member.status != MemberStatus::Approved {
+        return err!(RoleManagementError::NotApprovedMember);
+    }
+    // No-op if already the desired role
+    if member.role == new_role {
+        return err!(RoleManagementError::AlreadyRole);
+    }
+    // The admin role follows the community's admin set
+    if new_role == MemberRole::Admin || member.role == MemberRole::Admin {
+        return err!(AdminSetError::UseAdminInstructions);
+    }
+    // A role change resets any customised permissions to the new role's preset
+    let permissions = role_permissions(new_role);
+    member.role = new_role;
+    member.permissions = permissions;
+    emit!(MemberRoleChanged {
+        community,
+        member_wallet: member.wallet,
+        new_role,
+        permissions,
+        changed_by,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Shared by set_member_permissions and executed SetMemberPermissions actions
+fn apply_permissions_change(community: Pubkey, member: &mut Member, permissions: u16, changed_by: Pubkey, now: i64) -> Result<()> {
+    if permissions & !ALL_PERMISSIONS != 0 {
+        return err!(PermissionError::InvalidPermissions);
+    }
+    if member.status != MemberStatus::Approved {
+        return err!(RoleManagementError::NotApprovedMember);
+    }
+    if member.role == MemberRole::Admin {
+        return err!(AdminSetError::UseAdminInstructions);
+    }
+    member.permissions = permissions;
+    emit!(MemberPermissionsChanged {
+        community,
+        member_wallet: member.wallet,
+        permissions,
+        changed_by,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Shared by remove_member and executed RemoveMember actions
+fn apply_remove_member(community: &mut Community, community_key: Pubkey, member: &mut Member, removed_by: Pubkey, now: i64) -> Result<()> {
+    if member.status != MemberStatus::Approved {
+        return err!(AdminActionError::NotApproved);
+    }
+    if is_admin(community, &member.wallet) {
+        return err!(AdminSetError::UseAdminInstructions);
+    }
+    member.status = MemberStatus::Removed;
+    // Decrement member_count safely
+    if community.member_count > 0 {
+        community.member_count -= 1;
+    }
+    msg!("Member {} removed by {}", member.wallet, removed_by);
+    emit!(MemberRemoved {
+        community: community_key,
+        member_wallet: member.wallet,
+        admin: removed_by,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Shared by dissolve_community and executed DissolveCommunity actions
+fn apply_dissolve(community: &mut Community, community_key: Pubkey, dissolved_by: Pubkey, now: i64) -> Result<()> {
+    if community.status == CommunityStatus::Dissolved {
+        return err!(AdminActionError::CommunityDissolved);
+    }
+    community.status = CommunityStatus::Dissolved;
+    msg!("Community {} dissolved by {}", community_key, dissolved_by);
+    emit!(CommunityDissolved {
+        community: community_key,
+        admin: dissolved_by,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Keep a wallet's Member account, if it has one, in step with the admin set
+fn set_member_role(info: &AccountInfo, role: MemberRole) -> Result<()> {
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(());
+    }
+    let mut member = Member::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    member.role = role;
+    member.permissions = role_permissions(role);
+    member.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])
+}
+
+// Address of `wallet`'s Member account in `community`
+pub fn member_address(community: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"member", community.as_ref(), wallet.as_ref()], &crate::ID).0
+}
+
+fn is_admin(community: &Community, key: &Pubkey) -> bool {
+    community.admins.contains(key)
+}
+
+// Permissions `authority` holds in `community`. Community admins hold every permission;
+// anyone else holds what their approved Member account grants.
+fn permissions_of(
+    community: &Community,
+    community_key: Pubkey,
+    authority: Pubkey,
+    authority_member: Option<&Member>,
+) -> u16 {
+    if is_admin(community, &authority) {
+        return ALL_PERMISSIONS;
+    }
+    match authority_member {
+        Some(member)
+            if member.community == community_key
+                && member.wallet == authority
+                && member.status == MemberStatus::Approved =>
+        {
+            // The Admin role only counts while the wallet holds a seat; a governance
+            // ReplaceAdmin can take the seat without touching the Member account
+            if member.role == MemberRole::Admin {
+                role_permissions(MemberRole::Member)
+            } else {
+                member.permissions
+            }
+        }
+        _ => 0,
+    }
+}
+
+// Role and permission changes need PERMISSION_MANAGE_ROLES, and the signer
+// can only hand out permissions they hold themselves
+fn authorize_grant(held: u16, granted: u16) -> Result<()> {
+    if held & PERMISSION_MANAGE_ROLES == 0 {
+        return err!(PermissionError::MissingPermission);
+    }
+    if granted & !held != 0 {
+        return err!(PermissionError::PermissionEscalation);
+    }
+    Ok(())
+}
+
+// Add an approved member's wallet to the admin set, which stays bounded by MAX_ADMINS
+fn add_to_admins(community: &mut Community, member: &mut Member) -> Result<()> {
+    if member.status != MemberStatus::Approved {
+        return err!(RoleManagementError::NotApprovedMember);
+    }
+    if is_admin(community, &member.wallet) {
+        return err!(AdminSetError::AlreadyAdmin);
+    }
+    if community.admins.len() >= MAX_ADMINS {
+        return err!(AdminSetError::TooManyAdmins);
+    }
+    community.admins.push(member.wallet);
+    member.role = MemberRole::Admin;
+    member.permissions = ALL_PERMISSIONS;
+    Ok(())
+}
+
+// Remove `wallet` from the admin set, which may never become empty nor drop below
+// the approval threshold
+fn remove_from_admins(community: &mut Community, wallet: Pubkey) -> Result<()> {
+    let Some(position) = community.admins.iter().position(|admin| *admin == wallet) else {
+        return err!(AdminSetError::NotAnAdmin);
+    };
+    if community.admins.len() == 1 {
+        return err!(AdminSetError::LastAdmin);
+    }
+    if community.admins.len() - 1 < community.admin_threshold as usize {
+        return err!(MultisigError::BelowThreshold);
+    }
+    community.admins.remove(position);
+    // A transfer of the seat they no longer hold can't go through
+    if matches!(&community.pending_admin, Some(transfer) if transfer.from == wallet) {
+        community.pending_admin = None;
+    }
+    Ok(())
+}
+
+// Only the creator or a holder of PERMISSION_CLOSE_QUESTIONS may cancel, and only while voting is open
+fn ensure_question_cancellable(
+    voting_question: &VotingQuestion,
+    community: &Community,
+    authority: Pubkey,
+    permissions: u16, // Held by `authority`
+    reason: &str,
+    now: i64,
+) -> Result<()> {
+    if authority != voting_question.creator && permissions & PERMISSION_CLOSE_QUESTIONS == 0 {
+        return err!(QuestionCloseError::NotAuthorized);
+    }
+    if community.status == CommunityStatus::Dissolved {
+        return err!(EdgeCaseError::CommunityDissolved);
+    }
+    if reason.len() > MAX_CANCEL_REASON_LEN {
+        return err!(QuestionCloseError::ReasonTooLong);
+    }
+    if !voting_question.is_active || voting_question.is_finalized {
+        return err!(QuestionCloseError::AlreadyClosed);
+    }
+    if now > voting_question.deadline {
+        return err!(QuestionCloseError::VotingEnded);
+    }
+    Ok(())
+}
+
+fn require_permission(
+    community: &Account<Community>,
+    authority: Pubkey,
+    authority_member: Option<&Member>,
+    permission: u16,
+) -> Result<()> {
+    if permissions_of(community, community.key(), authority, authority_member) & permission != permission {
+        return err!(PermissionError::MissingPermission);
+    }
+    Ok(())
+}
+
+// A Member account can only go once nothing else refers to it: no one delegates to it
+// and it has no delegation of its own outstanding, in any scope (revoking needs both Members)
+fn ensure_member_closable(member: &Member) -> Result<()> {
+    if member.delegations_received > 0 {
+        return err!(CloseError::HoldsDelegations);
+    }
+    if member.delegations_given > 0 {
+        return err!(CloseError::HasDelegation);
+    }
+    Ok(())
+}
+
+// Only a rejected or removed member's account may be closed, by the member or a
+// holder of PERMISSION_REMOVE_MEMBERS
+fn ensure_member_close_allowed(member: &Member, closer: Pubkey, permissions: u16) -> Result<()> {
+    if closer != member.wallet && permissions & PERMISSION_REMOVE_MEMBERS == 0 {
+        return err!(CloseError::NotAuthorized);
+    }
+    if !matches!(member.status, MemberStatus::Rejected | MemberStatus::Removed) {
+        return err!(CloseError::MemberStillActive);
+    }
+    ensure_member_closable(member)
+}
+
+// Whoever paid for a vote account: the delegate for a delegation marker, otherwise the voter
+fn vote_rent_payer(vote: &Vote) -> Pubkey {
+    vote.delegated_to.unwrap_or(vote.voter)
+}
+
+// Joins, new questions and ballots need an active community
+fn ensure_community_active(community: &Community) -> Result<()> {
+    match community.status {
+        CommunityStatus::Active => Ok(()),
+        CommunityStatus::Paused => err!(EdgeCaseError::CommunityPaused),
+        CommunityStatus::Dissolved => err!(EdgeCaseError::CommunityDissolved),
+    }
+}
+
+// Replace the old u32::MAX "dissolved" sentinel with the status field. The real member
+// count it overwrote is gone, so a dissolved community migrates with zero members.
+fn replace_dissolved_sentinel(community: &mut Community) {
+    if community.member_count == u32::MAX {
+        community.status = CommunityStatus::Dissolved;
+        community.member_count = 0;
+    }
+}
+
+// Normalize a community name for its address: lowercase letters and digits of any script,
+// with every run of other characters collapsed to a single '-'.
+// "My  Community!" and "my-community" both map to "my-community"; "Café" stays "café".
+pub fn community_slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    // Lowercasing can lengthen a name, so cut back to the limit on a character boundary
+    let mut end = slug.len().min(MAX_SLUG_LEN);
+    while !slug.is_char_boundary(end) {
+        end -= 1;
+    }
+    slug.truncate(end);
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+// PDA seed for a slug: its SHA-256, so multi-byte characters never overrun the seed length limit
+pub fn community_seed(slug: &str) -> [u8; 32] {
+    hash(slug.as_bytes()).to_bytes()
+}
+
+// Address of the community registered under `name` (or any name normalizing to the same slug)
+pub fn community_address(name: &str) -> Pubkey {
+    Pubkey::find_program_address(&[b"community", &community_seed(&community_slug(name))], &crate::ID).0
+}
+
+// Bounds on question text, matching the `max_len` limits the account is sized for
+fn validate_question_text(question: &str, options: &[String]) -> Result<()> {
+    if question.len() > MAX_QUESTION_LEN {
+        return err!(VotingQuestionError::QuestionTooLong);
+    }
+    if options.len() < MIN_OPTIONS || options.len() > MAX_OPTIONS {
+        return err!(VotingQuestionError::TooManyOptions);
+    }
+    if options.iter().any(|option| option.len() > MAX_OPTION_LEN) {
+        return err!(VotingQuestionError::OptionTooLong);
+    }
+    Ok(())
+}
+
+// Ballots are accepted from the question's start time until its deadline
+fn ensure_voting_window(voting_question: &VotingQuestion, now: i64) -> Result<()> {
+    if !voting_question.is_active {
+        return err!(VotingQuestionError::NotActive);
+    }
+    if matches!(voting_question.starts_at, Some(starts_at) if now < starts_at) {
+        return err!(VotingQuestionError::VotingNotStarted);
+    }
+    if now > voting_question.deadline {
+        return err!(VotingQuestionError::DeadlinePassed);
+    }
+    Ok(())
+}
+
+// Deadline of a new question whose voting opens at `opens_at`, defaulting to the
+// community's voting period and bounded by its minimum and maximum duration
+fn question_deadline(config: &CommunityConfig, deadline: Option<i64>, opens_at: i64) -> Result<i64> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => opens_at.checked_add(config.voting_period).ok_or(VotingQuestionError::InvalidDeadline)?,
+    };
+    if deadline <= opens_at {
+        return err!(VotingQuestionError::InvalidDeadline);
+    }
+    if deadline - opens_at < config.min_voting_period {
+        return err!(VotingQuestionError::VotingPeriodTooShort);
+    }
+    if deadline - opens_at > config.voting_period {
+        return err!(VotingQuestionError::VotingPeriodTooLong);
+    }
+    Ok(deadline)
+}
+
+// Shared validation for create_community and update_community_config
+fn validate_config(config: &CommunityConfig) -> Result<()> {
+    if config.voting_period <= 0 {
+        return err!(VotingError::InvalidVotingPeriod);
+    }
+    if config.min_voting_period < 0 || config.min_voting_period > config.voting_period {
+        return err!(VotingError::InvalidMinVotingPeriod);
+    }
+    if (config.max_options as usize) < MIN_OPTIONS || config.max_options as usize > MAX_OPTIONS {
+        return err!(VotingError::InvalidMaxOptions);
+    }
+    match config.quorum {
+        QuorumRule::None => {}
+        QuorumRule::Absolute { min_ballots } => {
+            if min_ballots == 0 {
+                return err!(VotingError::InvalidQuorum);
+            }
+        }
+        QuorumRule::PercentOfMembers { bps } => {
+            if bps == 0 || bps > 10_000 {
+                return err!(VotingError::InvalidQuorum);
+            }
+        }
+    }
+    if let PassThreshold::Supermajority { bps } = config.pass_threshold {
+        if bps <= 5_000 || bps > 10_000 {
+            return err!(VotingError::InvalidPassThreshold);
+        }
+    }
+    Ok(())
+}
+
+// Indices of every option sharing the highest non-zero count
+fn winning_options(vote_counts: &[u64]) -> Vec<u8> {
+    let top = vote_counts.iter().copied().max().unwrap_or(0);
+    if top == 0 {
+        return Vec::new();
+    }
+    vote_counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count == top)
+        .map(|(index, _)| index as u8)
+        .collect()
+}
+
+// Share of eligible voters that cast a ballot, in basis points (capped at 100%)
+fn turnout_bps(total_ballots: u64, eligible_voters: u32) -> u16 {
+    if eligible_voters == 0 {
+        return 0;
+    }
+    let bps = total_ballots.saturating_mul(10_000) / eligible_voters as u64;
+    bps.min(10_000) as u16
+}
+
+// Take the next position in the community's question sequence
+fn next_question_index(community: &mut Community) -> Result<u64> {
+    let index = community.question_count;
+    community.question_count = index.checked_add(1).ok_or(VotingError::CounterOverflow)?;
+    Ok(index)
+}
+
+// Balance of the voter's token account in the question's voting mint
+fn token_balance_weight(token_account: Option<&TokenAccount>, mint: Pubkey, voter: Pubkey) -> Result<u64> {
+    let token_account = match token_account {
+        Some(token_account) => token_account,
+        None => return err!(WeightError::MissingTokenAccount),
+    };
+    if token_account.mint != mint {
+        return err!(WeightError::WrongMint);
+    }
+    if token_account.owner != voter {
+        return err!(WeightError::WrongTokenOwner);
+    }
+    Ok(token_account.amount)
+}
+
+// Tokens the voter has locked in the community's escrow for the question's voting mint
+fn escrow_weight(
+    escrow: Option<&VoterEscrow>,
+    community: Pubkey,
+    mint: Pubkey,
+    voter: Pubkey,
+) -> Result<u64> {
+    let escrow = match escrow {
+        Some(escrow) => escrow,
+        None => return err!(WeightError::MissingEscrow),
+    };
+    if escrow.community != community || escrow.owner != voter {
+        return err!(EscrowError::WrongEscrow);
+    }
+    if escrow.mint != mint {
+        return err!(WeightError::WrongMint);
+    }
+    Ok(escrow.amount)
+}
+
+// Add a deposit of the community's voting mint to an escrow
+fn lock_tokens(escrow: &mut VoterEscrow, mint: Pubkey, amount: u64) -> Result<()> {
+    if amount == 0 {
+        return err!(EscrowError::InvalidAmount);
+    }
+    // An escrow still holding tokens of a previous voting mint must be emptied first
+    if escrow.amount > 0 && escrow.mint != mint {
+        return err!(EscrowError::WrongMint);
+    }
+    escrow.mint = mint;
+    escrow.amount = escrow.amount.checked_add(amount).ok_or(EscrowError::InvalidAmount)?;
+    Ok(())
+}
+
+// Take a withdrawal out of an escrow once every question voted on with it has closed
+fn unlock_tokens(escrow: &mut VoterEscrow, amount: u64, now: i64) -> Result<()> {
+    if amount == 0 {
+        return err!(EscrowError::InvalidAmount);
+    }
+    if amount > escrow.amount {
+        return err!(EscrowError::InsufficientEscrow);
+    }
+    // Blocked while any question voted on with these tokens is still open
+    if now <= escrow.locked_until {
+        return err!(EscrowError::TokensLocked);
+    }
+    escrow.amount -= amount;
+    Ok(())
+}
+
+// Voting weight for the ballot being cast, per the question's weighting rule
+fn ballot_weight(accounts: &CastVote) -> Result<u64> {
+    let weight = match accounts.voting_question.vote_weighting {
+        VoteWeighting::OnePerMember => 1,
+        VoteWeighting::TokenBalance { mint } => token_balance_weight(
+            accounts.voter_token_account.as_deref(),
+            mint,
+            accounts.voter.key(),
+        )?,
+        VoteWeighting::LockedTokens { mint } => escrow_weight(
+            accounts.voter_escrow.as_deref(),
+            accounts.voting_question.community,
+            mint,
+            accounts.voter.key(),
+        )?,
+    };
+    if weight == 0 {
+        return err!(WeightError::NoVotingPower);
+    }
+    Ok(weight)
+}
+
+// Check a ballot's selections against the question's ballot type and options
+fn validate_selection(
+    voting_question: &VotingQuestion,
+    selected_options: &[u8],
+    allocation: &[u16],
+) -> Result<()> {
+    match voting_question.ballot_type {
+        BallotType::SingleChoice => {
+            if selected_options.len() != 1 {
+                return err!(VotingQuestionError::WrongBallotType);
+            }
+        }
+        BallotType::MultipleChoice { min_selections, max_selections } => {
+            if selected_options.len() < min_selections as usize
+                || selected_options.len() > max_selections as usize
+            {
+                return err!(VotingQuestionError::InvalidSelectionCount);
+            }
+        }
+        BallotType::RankedChoice => {
+            // Rankings must order every option
+            if selected_options.len() != voting_question.options.len() {
+                return err!(VotingQuestionError::InvalidSelectionCount);
+            }
+        }
+        BallotType::Quadratic { voice_credits } => {
+            if allocation.len() != voting_question.options.len() || selected_options.is_empty() {
+                return err!(VotingQuestionError::InvalidAllocation);
+            }
+            if quadratic_cost(allocation) > voice_credits as u64 {
+                return err!(VotingQuestionError::InsufficientVoiceCredits);
+            }
+        }
+    }
+    let mut seen: u32 = 0;
+    for &option in selected_options {
+        if option as usize >= voting_question.options.len() {
+            return err!(VotingQuestionError::InvalidOption);
+        }
+        if seen & (1 << option) != 0 {
+            return err!(VotingQuestionError::DuplicateOption);
+        }
+        seen |= 1 << option;
+    }
+    Ok(())
+}
+
+// Amount a ballot adds to each option's running tally
+fn ballot_contributions(
+    ballot_type: BallotType,
+    selected_options: &[u8],
+    allocation: &[u16],
+    weight: u64,
+) -> Result<Vec<(u8, u64)>> {
+    match ballot_type {
+        // Ranked ballots only count towards their first preference until the runoff
+        BallotType::RankedChoice => Ok(vec![(selected_options[0], weight)]),
+        BallotType::Quadratic { .. } => selected_options
+            .iter()
+            .map(|&option| {
+                let votes = allocation[option as usize] as u64;
+                let effective = votes.checked_mul(weight).ok_or(VotingQuestionError::TallyOverflow)?;
+                Ok((option, effective))
+            })
+            .collect(),
+        _ => Ok(selected_options.iter().map(|&option| (option, weight)).collect()),
+    }
+}
+
+// Add (or remove) a ballot's contributions and counts to the question's running tally
+fn adjust_tally(
+    voting_question: &mut VotingQuestion,
+    contributions: Vec<(u8, u64)>,
+    ballots: u64,
+    weight: u64,
+    add: bool,
+) -> Result<()> {
+    let apply = |value: u64, amount: u64| -> Result<u64> {
+        let updated = if add { value.checked_add(amount) } else { value.checked_sub(amount) };
+        Ok(updated.ok_or(VotingQuestionError::TallyOverflow)?)
+    };
+    for (option, amount) in contributions {
+        let count = voting_question
+            .vote_counts
+            .get_mut(option as usize)
+            .ok_or(VotingQuestionError::InvalidOption)?;
+        *count = apply(*count, amount)?;
+    }
+    voting_question.total_ballots = apply(voting_question.total_ballots, ballots)?;
+    voting_question.total_weight = apply(voting_question.total_weight, weight)?;
+    Ok(())
+}
+
+// Record a new delegation on both members. Delegation stays a single hop across every
+// scope: a member holding delegations can't delegate, and a delegating member can't receive.
+fn link_delegation(delegator_member: &mut Member, delegate_member: &mut Member) -> Result<()> {
+    if delegate_member.wallet == delegator_member.wallet {
+        return err!(DelegationError::SelfDelegation);
+    }
+    if delegator_member.delegations_received > 0 {
+        return err!(DelegationError::DelegatorHoldsDelegations);
+    }
+    if delegate_member.delegations_given > 0 {
+        return err!(DelegationError::DelegateHasDelegated);
+    }
+    delegator_member.delegations_given = delegator_member
+        .delegations_given
+        .checked_add(1)
+        .ok_or(ProgramError::InvalidArgument)?;
+    delegate_member.delegations_received = delegate_member
+        .delegations_received
+        .checked_add(1)
+        .ok_or(ProgramError::InvalidArgument)?;
+    Ok(())
+}
+
+// Undo link_delegation when a delegation is revoked
+fn unlink_delegation(delegator_member: &mut Member, delegate_member: &mut Member) {
+    delegator_member.delegations_given = delegator_member.delegations_given.saturating_sub(1);
+    delegate_member.delegations_received = delegate_member.delegations_received.saturating_sub(1);
+}
+
+// Check that a delegation hands its delegator's weight to `delegate` on `question`.
+// `scoped_override` is set when the delegator also holds a delegation for this very
+// question, which takes precedence over their global one.
+fn check_delegation_applies(
+    delegation: &Delegation,
+    community: Pubkey,
+    question: Pubkey,
+    delegate: Pubkey,
+    scoped_override: bool,
+) -> Result<()> {
+    if delegation.community != community || delegation.delegate != delegate {
+        return err!(DelegationError::WrongDelegate);
+    }
+    match delegation.question {
+        Some(scope) if scope != question => err!(DelegationError::OutOfScope),
+        None if scoped_override => err!(DelegationError::OutOfScope),
+        _ => Ok(()),
+    }
+}
+
+// The delegator's weight follows the delegate's ballot, counting as one more ballot
+fn add_delegated_weight(voting_question: &mut VotingQuestion, vote: &mut Vote, weight: u64) -> Result<()> {
+    let contributions =
+        ballot_contributions(voting_question.ballot_type, &vote.selected_options, &vote.allocation, weight)?;
+    adjust_tally(voting_question, contributions, 1, weight, true)?;
+    vote.weight = vote.weight.checked_add(weight).ok_or(VotingQuestionError::TallyOverflow)?;
+    vote.delegated_weight = vote
+        .delegated_weight
+        .checked_add(weight)
+        .ok_or(VotingQuestionError::TallyOverflow)?;
+    Ok(())
+}
+
+// Take an overriding delegator's weight back off the delegate's ballot
+fn remove_delegated_weight(voting_question: &mut VotingQuestion, vote: &mut Vote, weight: u64) -> Result<()> {
+    let contributions =
+        ballot_contributions(voting_question.ballot_type, &vote.selected_options, &vote.allocation, weight)?;
+    adjust_tally(voting_question, contributions, 1, weight, false)?;
+    vote.weight = vote.weight.checked_sub(weight).ok_or(VotingQuestionError::TallyOverflow)?;
+    vote.delegated_weight = vote
+        .delegated_weight
+        .checked_sub(weight)
+        .ok_or(VotingQuestionError::TallyOverflow)?;
+    Ok(())
+}
+
+// Count each delegation group in `remaining_accounts` towards the delegate's direct `vote`,
+// leaving a marker in the delegator's vote PDA so they can't be counted twice
+fn claim_delegations<'info>(
+    voting_question: &mut Account<'info, VotingQuestion>,
+    vote: &mut Vote,
+    payer: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    now: i64,
+) -> Result<()> {
+    let voter = vote.voter;
+    let question_key = voting_question.key();
+    if voting_question.reveal_deadline.is_some() {
+        return err!(DelegationError::SecretBallot);
+    }
+    let group = match voting_question.vote_weighting {
+        VoteWeighting::OnePerMember => 4,
+        VoteWeighting::LockedTokens { .. } => 5,
+        VoteWeighting::TokenBalance { .. } => return err!(DelegationError::UnsupportedWeighting),
+    };
+    let groups = remaining_accounts.chunks_exact(group);
+    if !groups.remainder().is_empty() {
+        return err!(DelegationError::MissingAccounts);
+    }
+
+    let mut claimed_weight: u64 = 0;
+    let mut delegators: u32 = 0;
+    for accounts in groups {
+        let delegation: Account<Delegation> = Account::try_from(&accounts[0])?;
+        let delegator_member: Account<Member> = Account::try_from(&accounts[1])?;
+        let marker_info = &accounts[2];
+        // A delegation made for this very question overrides the delegator's global one
+        let (scoped_key, _) = Pubkey::find_program_address(
+            &[
+                b"delegation",
+                delegation.community.as_ref(),
+                delegation.delegator.as_ref(),
+                question_key.as_ref(),
+            ],
+            &crate::ID,
+        );
+        if accounts[3].key() != scoped_key {
+            return err!(DelegationError::WrongMember);
+        }
+        let scoped_override = accounts[3].owner == &crate::ID && !accounts[3].data_is_empty();
+        check_delegation_applies(&delegation, voting_question.community, question_key, voter, scoped_override)?;
+        if delegator_member.community != delegation.community
+            || delegator_member.wallet != delegation.delegator
+        {
+            return err!(DelegationError::WrongMember);
+        }
+        if delegator_member.status != MemberStatus::Approved {
+            return err!(DelegationError::DelegatorInactive);
+        }
+
+        // The delegator's own vote PDA must still be empty; it becomes the marker
+        let (marker_key, marker_bump) = Pubkey::find_program_address(
+            &[b"vote", question_key.as_ref(), delegation.delegator.as_ref()],
+            &crate::ID,
+        );
+        if marker_info.key() != marker_key {
+            return err!(DelegationError::WrongMember);
+        }
+        if !marker_info.data_is_empty() {
+            return err!(DelegationError::DelegatorAlreadyVoted);
+        }
+
+        let weight = match voting_question.vote_weighting {
+            VoteWeighting::LockedTokens { mint } => {
+                let mut escrow: Account<VoterEscrow> = Account::try_from(&accounts[4])?;
+                let weight =
+                    escrow_weight(Some(&escrow), voting_question.community, mint, delegation.delegator)?;
+                escrow.locked_until = escrow.locked_until.max(voting_question.deadline);
+                escrow.exit(&crate::ID)?;
+                weight
+            }
+            _ => 1,
+        };
+        if weight == 0 {
+            return err!(WeightError::NoVotingPower);
+        }
+
+        create_pda_account(
+            payer,
+            marker_info,
+            system,
+            VOTE_SPACE,
+            &[b"vote", question_key.as_ref(), delegation.delegator.as_ref(), &[marker_bump]],
+        )?;
+        let marker = Vote {
+            version: VOTE_VERSION,
+            question: question_key,
+            voter: delegation.delegator,
+            selected_option: 0,
+            voted_at: now,
+            selected_options: Vec::new(),
+            tallied_round: 0,
+            weight,
+            allocation: Vec::new(),
+            delegated_to: Some(voter),
+            delegated_weight: 0,
+            commitment: None,
+            revealed: false,
+        };
+        marker.try_serialize(&mut &mut marker_info.try_borrow_mut_data()?[..])?;
+
+        add_delegated_weight(voting_question, vote, weight)?;
+        claimed_weight = claimed_weight.checked_add(weight).ok_or(VotingQuestionError::TallyOverflow)?;
+        delegators += 1;
+    }
+    emit!(DelegatedVotesClaimed {
+        question: question_key,
+        delegate: voter,
+        delegators,
+        weight: claimed_weight,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+// Create a program-owned PDA passed in as a remaining account.
+// Handles addresses that were pre-funded to block a plain create_account.
+fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    target: &AccountInfo<'info>,
+    system: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let signer: &[&[&[u8]]] = &[seeds];
+    let current = target.lamports();
+    if current == 0 {
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system.clone(),
+                system_program::CreateAccount { from: payer.clone(), to: target.clone() },
+                signer,
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        )?;
+    } else {
+        if rent > current {
+            system_program::transfer(
+                CpiContext::new(
+                    system.clone(),
+                    system_program::Transfer { from: payer.clone(), to: target.clone() },
+                ),
+                rent - current,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system.clone(),
+                system_program::Allocate { account_to_allocate: target.clone() },
+                signer,
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system.clone(),
+                system_program::Assign { account_to_assign: target.clone() },
+                signer,
+            ),
+            &crate::ID,
+        )?;
+    }
+    Ok(())
+}
+
+// Voice credits spent by a quadratic allocation: the sum of squared votes
+fn quadratic_cost(allocation: &[u16]) -> u64 {
+    allocation.iter().map(|&votes| votes as u64 * votes as u64).sum()
+}
+
+// Checks shared by every way of casting a ballot. Returns the voter's weight after
+// locking any escrowed tokens and reclaiming weight a delegate counted for this voter.
+fn open_ballot(accounts: &mut CastVote, now: i64) -> Result<u64> {
+    let voting_question = &accounts.voting_question;
+    let member = &accounts.member;
+    let community = &accounts.community;
+    ensure_community_active(community)?;
+    // Prevent if member is not approved
+    if member.status != MemberStatus::Approved {
+        return err!(EdgeCaseError::MemberInactive);
+    }
+    ensure_voting_window(voting_question, now)?;
+    // Existing vote accounts are either a direct vote or a marker left by a delegate
+    if accounts.vote.voter != Pubkey::default() && accounts.vote.delegated_to.is_none() {
+        return err!(VotingQuestionError::AlreadyVoted);
+    }
+    let weight = ballot_weight(accounts)?;
+    // Locked tokens can't be withdrawn and re-used until this question has closed
+    if let Some(escrow) = &mut accounts.voter_escrow {
+        if let VoteWeighting::LockedTokens { .. } = accounts.voting_question.vote_weighting {
+            let closes_at = accounts
+                .voting_question
+                .reveal_deadline
+                .unwrap_or(accounts.voting_question.deadline);
+            escrow.locked_until = escrow.locked_until.max(closes_at);
+        }
+    }
+    let voting_question = &mut accounts.voting_question;
+
+    // Voting directly overrides the delegation: take this member's weight back from the delegate
+    if let Some(delegate) = accounts.vote.delegated_to {
+        let delegate_vote = match &mut accounts.delegate_vote {
+            Some(delegate_vote)
+                if delegate_vote.question == voting_question.key() && delegate_vote.voter == delegate =>
+            {
+                delegate_vote
+            }
+            _ => return err!(DelegationError::WrongDelegateVote),
+        };
+        let reclaimed = accounts.vote.weight;
+        remove_delegated_weight(voting_question, delegate_vote, reclaimed)?;
+        // The delegate paid for the marker, which now becomes the voter's own ballot:
+        // the voter buys it from them, so closing it later rightly refunds the voter
+        let delegate_wallet = match &accounts.delegate_wallet {
+            Some(wallet) if wallet.key() == delegate => wallet.to_account_info(),
+            _ => return err!(DelegationError::WrongDelegateWallet),
+        };
+        system_program::transfer(
+            CpiContext::new(
+                accounts.system_program.to_account_info(),
+                system_program::Transfer { from: accounts.voter.to_account_info(), to: delegate_wallet },
+            ),
+            accounts.vote.to_account_info().lamports(),
+        )?;
+        emit!(DelegationOverridden {
+            question: voting_question.key(),
+            delegator: accounts.voter.key(),
+            delegate,
+            weight: reclaimed,
+            timestamp: now,
+        });
+    }
+    Ok(weight)
+}
+
+// Validate a ballot against the question and add it, along with the weight of any
+// delegations passed as remaining accounts, to the running tally
+fn record_ballot<'info>(
+    accounts: &mut CastVote<'info>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    selected_options: Vec<u8>,
+    allocation: Vec<u16>,
+) -> Result<()> {
+    let voting_question = &accounts.voting_question;
+    let now = Clock::get()?.unix_timestamp;
+    if voting_question.reveal_deadline.is_some() {
+        return err!(CommitRevealError::CommitRequired);
+    }
+    validate_selection(voting_question, &selected_options, &allocation)?;
+
+    let weight = open_ballot(accounts, now)?;
+    let voting_question = &mut accounts.voting_question;
+
+    // Update the on-chain tally in the same instruction that records the vote
+    let contributions =
+        ballot_contributions(voting_question.ballot_type, &selected_options, &allocation, weight)?;
+    adjust_tally(voting_question, contributions, 1, weight, true)?;
+    let vote = &mut accounts.vote;
+    vote.version = VOTE_VERSION;
+    vote.question = voting_question.key();
+    vote.voter = accounts.voter.key();
+    vote.selected_option = selected_options[0];
+    vote.selected_options = selected_options;
+    vote.voted_at = now;
+    vote.tallied_round = 0;
+    vote.weight = weight;
+    vote.allocation = allocation;
+    vote.delegated_to = None;
+    vote.delegated_weight = 0;
+    vote.commitment = None;
+    vote.revealed = false;
+    emit!(VoteCast {
+        question: vote.question,
+        voter: vote.voter,
+        selected_option: vote.selected_option,
+        selected_options: vote.selected_options.clone(),
+        allocation: vote.allocation.clone(),
+        weight,
+        timestamp: now,
+    });
+    if !remaining_accounts.is_empty() {
+        claim_delegations(
+            &mut accounts.voting_question,
+            &mut accounts.vote,
+            &accounts.voter.to_account_info(),
+            &accounts.system_program.to_account_info(),
+            remaining_accounts,
+            now,
+        )?;
+    }
+    Ok(())
+}
+
+// Checks before a recorded direct ballot may be changed or retracted
+fn ensure_vote_editable(
+    voting_question: &VotingQuestion,
+    community: &Community,
+    vote: &Vote,
+    now: i64,
+) -> Result<()> {
+    ensure_community_active(community)?;
+    if !voting_question.is_active {
+        return err!(VotingQuestionError::NotActive);
+    }
+    if now > voting_question.deadline {
+        return err!(VotingQuestionError::DeadlinePassed);
+    }
+    // Secret ballots aren't in the tally until revealed, so there is nothing to amend
+    if voting_question.reveal_deadline.is_some() {
+        return err!(VoteChangeError::SecretBallot);
+    }
+    if vote.delegated_to.is_some() {
+        return err!(VoteChangeError::NotDirectVote);
+    }
+    Ok(())
+}
+
+// Commitment a secret ballot must match when revealed
+fn vote_commitment(question: &Pubkey, voter: &Pubkey, selected_option: u8, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[question.as_ref(), voter.as_ref(), &[selected_option], salt]).to_bytes()
+}
+
+// Open a secret ballot's commitment during the reveal period and tally it
+fn reveal_ballot(
+    voting_question: &mut VotingQuestion,
+    question_key: Pubkey,
+    vote: &mut Vote,
+    selected_option: u8,
+    salt: &[u8; 32],
+    now: i64,
+) -> Result<()> {
+    let reveal_deadline = match voting_question.reveal_deadline {
+        Some(reveal_deadline) => reveal_deadline,
+        None => return err!(CommitRevealError::NotSecretBallot),
+    };
+    if now <= voting_question.deadline || now > reveal_deadline || voting_question.is_finalized {
+        return err!(CommitRevealError::NotRevealPeriod);
+    }
+    let commitment = match vote.commitment {
+        Some(commitment) if !vote.revealed => commitment,
+        _ => return err!(CommitRevealError::NothingToReveal),
+    };
+    if selected_option as usize >= voting_question.options.len() {
+        return err!(VotingQuestionError::InvalidOption);
+    }
+    if vote_commitment(&question_key, &vote.voter, selected_option, salt) != commitment {
+        return err!(CommitRevealError::CommitmentMismatch);
+    }
+    // Only revealed ballots are tallied
+    adjust_tally(voting_question, vec![(selected_option, vote.weight)], 1, vote.weight, true)?;
+    vote.selected_option = selected_option;
+    vote.selected_options = vec![selected_option];
+    vote.revealed = true;
+    Ok(())
+}
+
+// Apply the question's quorum and pass threshold to its final tally
+fn question_outcome(
+    voting_question: &VotingQuestion,
+    vote_counts: &[u64],
+    winners: &[u8],
+) -> QuestionOutcome {
+    // Quorum counts people; the pass threshold counts weight
+    let ballots = voting_question.total_ballots as u128;
+    let quorum_met = match voting_question.quorum {
+        QuorumRule::None => true,
+        QuorumRule::Absolute { min_ballots } => ballots >= min_ballots as u128,
+        QuorumRule::PercentOfMembers { bps } => {
+            ballots * 10_000 >= bps as u128 * voting_question.eligible_voters as u128
+        }
+    };
+    if !quorum_met {
+        return QuestionOutcome::QuorumNotMet;
+    }
+    // A tie (or no votes at all) never passes
+    if winners.len() != 1 {
+        return QuestionOutcome::Failed;
+    }
+    // Quadratic ballots spread several effective votes each, so compare against all of them
+    let total = match voting_question.ballot_type {
+        BallotType::Quadratic { .. } => vote_counts.iter().map(|&count| count as u128).sum(),
+        _ => voting_question.total_weight as u128,
+    };
+    let leading = vote_counts[winners[0] as usize] as u128;
+    let passed = match voting_question.pass_threshold {
+        PassThreshold::SimpleMajority => leading * 2 > total,
+        PassThreshold::Supermajority { bps } => leading * 10_000 >= bps as u128 * total,
+    };
+    if passed {
+        QuestionOutcome::Passed
+    } else {
+        QuestionOutcome::Failed
+    }
+}
+
+// Highest-ranked option on a ballot that has not been eliminated yet
+fn top_preference(ranking: &[u8], eliminated: &[u8]) -> Option<u8> {
+    ranking.iter().copied().find(|option| !eliminated.contains(option))
+}
+
+// Close the current runoff round once every ballot has been counted in it:
+// either an option holds a majority, or every option tied for last is eliminated.
+fn close_runoff_round(runoff: &mut RunoffTally, total_weight: u64, option_count: usize) {
+    let remaining: Vec<u8> = (0..option_count as u8)
+        .filter(|option| !runoff.eliminated.contains(option))
+        .collect();
+    let counts = runoff.round_counts.clone();
+    let top = remaining.iter().map(|&o| counts[o as usize]).max().unwrap_or(0);
+    let bottom = remaining.iter().map(|&o| counts[o as usize]).min().unwrap_or(0);
+
+    let mut eliminated = Vec::new();
+    if top as u128 * 2 > total_weight as u128 || remaining.len() <= 1 {
+        runoff.winning_options = remaining
+            .iter()
+            .copied()
+            .filter(|&o| counts[o as usize] == top)
+            .collect();
+        runoff.is_complete = true;
+    } else if top == bottom {
+        // Every remaining option is tied; eliminating them all would leave no winner
+        runoff.winning_options = remaining;
+        runoff.is_complete = true;
+    } else {
+        eliminated = remaining
+            .iter()
+            .copied()
+            .filter(|&o| counts[o as usize] == bottom)
+            .collect();
+        runoff.eliminated.extend_from_slice(&eliminated);
+    }
+
+    runoff.rounds.push(RunoffRound {
+        vote_counts: counts,
+        eliminated,
+    });
+    if !runoff.is_complete {
+        runoff.round += 1;
+        runoff.round_counts = vec![0; option_count];
+        runoff.ballots_processed = 0;
+    }
+}
+
+// Reset the runoff crank to the first round of a question's count
+fn start_runoff(runoff: &mut RunoffTally, voting_question: &VotingQuestion) {
+    runoff.round = 0;
+    runoff.eliminated = Vec::new();
+    runoff.round_counts = vec![0; voting_question.options.len()];
+    runoff.ballots_processed = 0;
+    runoff.rounds = Vec::new();
+    runoff.winning_options = Vec::new();
+    runoff.is_complete = false;
+    // Nothing to count
+    if voting_question.total_ballots == 0 {
+        runoff.is_complete = true;
+    }
+}
+
+// Count one ballot in the current runoff round. Returns true when it was the round's
+// last ballot and the round has been closed.
+fn count_runoff_ballot(
+    runoff: &mut RunoffTally,
+    voting_question: &VotingQuestion,
+    ranking: &[u8],
+    weight: u64,
+) -> Result<bool> {
+    // Full rankings never exhaust while an option remains. Delegation markers
+    // carry no ranking; their weight is already on the delegate's ballot.
+    if let Some(option) = top_preference(ranking, &runoff.eliminated) {
+        let count = &mut runoff.round_counts[option as usize];
+        *count = count.checked_add(weight).ok_or(VotingQuestionError::TallyOverflow)?;
+    }
+    runoff.ballots_processed += 1;
+    if runoff.ballots_processed < voting_question.total_ballots {
+        return Ok(false);
+    }
+    close_runoff_round(runoff, voting_question.total_weight, voting_question.options.len());
+    Ok(true)
+}
+
+#[program]
+pub mod voting {
+    use super::*;
+
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn create_community(
+        ctx: Context<CreateCommunity>,
+        name: String,
+        description: String,
+        config: CommunityConfig,
+    ) -> Result<()> {
+        // Constraints
+        if name.len() > MAX_NAME_LEN {
+            return err!(VotingError::NameTooLong);
+        }
+        if description.len() > MAX_DESCRIPTION_LEN {
+            return err!(VotingError::DescriptionTooLong);
+        }
+        let slug = community_slug(&name);
+        if slug.is_empty() {
+            return err!(VotingError::InvalidName);
+        }
+        validate_config(&config)?;
+
+        let community = &mut ctx.accounts.community;
+        community.admins = vec![ctx.accounts.admin.key()];
+        community.name = name;
+        community.description = description;
+        community.member_count = 1;
+        community.created_at = Clock::get()?.unix_timestamp;
+        community.config = config;
+        community.question_count = 0;
+        community.slug = slug;
+        community.version = COMMUNITY_VERSION;
+        community.status = CommunityStatus::Active;
+        community.pending_admin = None;
+        community.admin_threshold = 1;
+        community.admin_proposal_count = 0;
+        let now = Clock::get()?.unix_timestamp;
+        emit!(CommunityCreated {
+            community: ctx.accounts.community.key(),
+            admin: ctx.accounts.admin.key(),
+            name: ctx.accounts.community.name.clone(),
+            slug: ctx.accounts.community.slug.clone(),
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn join_community(ctx: Context<JoinCommunity>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let member = &mut ctx.accounts.member;
+        ensure_community_active(community)?;
+        // Allow re-application only if previously rejected or removed.
+        // A freshly created account has no wallet yet and reads as Pending.
+        let is_new = member.wallet == Pubkey::default();
+        if !is_new && matches!(member.status, MemberStatus::Pending | MemberStatus::Approved) {
+            return err!(EdgeCaseError::AlreadyActive);
+        }
+        member.version = MEMBER_VERSION;
+        member.community = community.key();
+        member.wallet = ctx.accounts.user.key();
+        member.role = MemberRole::Member;
+        member.permissions = role_permissions(MemberRole::Member);
+        member.joined_at = Clock::get()?.unix_timestamp;
+        member.status = MemberStatus::Pending;
+        let now = Clock::get()?.unix_timestamp;
+        emit!(MemberJoined {
+            community: ctx.accounts.community.key(),
+            member_wallet: ctx.accounts.user.key(),
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn approve_member(
+        ctx: Context<ApproveMember>,
+        approve: bool, // true = approve, false = reject
+    ) -> Result<()> {
+        let admin = ctx.accounts.authority.key();
+        require_permission(
+            &ctx.accounts.community,
+            admin,
+            ctx.accounts.authority_member.as_deref(),
+            PERMISSION_APPROVE_MEMBERS,
+        )?;
+        let community = &mut ctx.accounts.community;
+        let member = &mut ctx.accounts.member;
+        // Only pending members can be approved/rejected
+        if member.status != MemberStatus::Pending {
+            return err!(ApprovalError::NotPending);
+        }
+        if approve {
+            member.status = MemberStatus::Approved;
+            community.member_count = community.member_count.checked_add(1).ok_or(ProgramError::InvalidArgument)?;
+            msg!("Member {} approved by {}", member.wallet, admin);
+        } else {
+            member.status = MemberStatus::Rejected;
+            msg!("Member {} rejected by {}", member.wallet, admin);
+        }
+        let now = Clock::get()?.unix_timestamp;
+        emit!(MemberApproved {
+            community: ctx.accounts.community.key(),
+            member_wallet: member.wallet,
+            admin,
+            status: member.status,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_voting_question(
+        ctx: Context<CreateVotingQuestion>,
+        question: String,
+        options: Vec<String>,
+        starts_at: Option<i64>, // Open voting later, after a discussion period; None opens it now
+        deadline: Option<i64>,  // None keeps voting open for the community's voting period
+        ballot_type: BallotType,
+        reveal_period: i64, // Seconds after the deadline to reveal secret ballots; 0 for open ballots
+        action: Option<CommunityAction>, // Governance: executable once option 0 (approve) passes
+    ) -> Result<()> {
+        let community = &ctx.accounts.community;
+        let member = &ctx.accounts.member;
+        ensure_community_active(community)?;
+        // Prevent if member is not approved
+        if member.status != MemberStatus::Approved {
+            return err!(EdgeCaseError::MemberInactive);
+        }
+        require_permission(community, ctx.accounts.creator.key(), Some(member), PERMISSION_CREATE_QUESTIONS)?;
+        let now = Clock::get()?.unix_timestamp;
+        validate_question_text(&question, &options)?;
+        let config = &community.config;
+        if options.len() > config.max_options as usize {
+            return err!(VotingQuestionError::TooManyOptions);
+        }
+        if matches!(starts_at, Some(starts_at) if starts_at < now) {
+            return err!(VotingQuestionError::InvalidStartTime);
+        }
+        // The voting period is measured from when voting opens
+        let deadline = question_deadline(config, deadline, starts_at.unwrap_or(now))?;
+        if let BallotType::MultipleChoice { min_selections, max_selections } = ballot_type {
+            if min_selections == 0
+                || min_selections > max_selections
+                || max_selections as usize > options.len()
+            {
+                return err!(VotingQuestionError::InvalidSelectionLimits);
+            }
+        }
+        if let BallotType::Quadratic { voice_credits } = ballot_type {
+            if voice_credits == 0 {
+                return err!(VotingQuestionError::InvalidVoiceCredits);
+            }
+        }
+        if reveal_period < 0 {
+            return err!(CommitRevealError::InvalidRevealPeriod);
+        }
+        if reveal_period > 0 && ballot_type != BallotType::SingleChoice {
+            return err!(CommitRevealError::SingleChoiceOnly);
+        }
+        if let Some(action) = &action {
+            require_permission(community, ctx.accounts.creator.key(), Some(member), PERMISSION_PROPOSE_ACTIONS)?;
+            validate_action_question(community, action, ballot_type, options.len(), starts_at.unwrap_or(now), deadline)?;
+        }
+        let reveal_deadline = if reveal_period > 0 {
+            Some(deadline.checked_add(reveal_period).ok_or(CommitRevealError::InvalidRevealPeriod)?)
+        } else {
+            None
+        };
+        let voting_question = &mut ctx.accounts.voting_question;
+        voting_question.version = QUESTION_VERSION;
+        voting_question.community = ctx.accounts.community.key();
+        voting_question.creator = ctx.accounts.creator.key();
+        voting_question.question = question;
+        voting_question.vote_counts = vec![0; options.len()];
+        voting_question.total_ballots = 0;
+        voting_question.eligible_voters = community.member_count;
+        voting_question.is_finalized = false;
+        // Snapshot the governance rules so later config changes can't move the goalposts
+        voting_question.quorum = community.config.quorum;
+        voting_question.pass_threshold = community.config.pass_threshold;
+        voting_question.ballot_type = ballot_type;
+        voting_question.vote_weighting = community.config.vote_weighting;
+        voting_question.total_weight = 0;
+        voting_question.reveal_deadline = reveal_deadline;
+        voting_question.total_commits = 0;
+        voting_question.starts_at = starts_at;
+        voting_question.action = action.clone();
+        voting_question.action_executed = false;
+        voting_question.options = options;
+        voting_question.deadline = deadline;
+        voting_question.created_at = now;
+        voting_question.is_active = true;
+        // The next question gets the next address in the sequence
+        let index = next_question_index(&mut ctx.accounts.community)?;
+        ctx.accounts.voting_question.index = index;
+        emit!(VotingQuestionCreated {
+            community: ctx.accounts.community.key(),
+            question: ctx.accounts.voting_question.key(),
+            creator: ctx.accounts.creator.key(),
+            index,
+            starts_at,
+            deadline: deadline,
+            action,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn cast_vote<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CastVote<'info>>,
+        selected_option: u8,
+    ) -> Result<()> {
+        if ctx.accounts.voting_question.ballot_type != BallotType::SingleChoice {
+            return err!(VotingQuestionError::WrongBallotType);
+        }
+        record_ballot(ctx.accounts, ctx.remaining_accounts, vec![selected_option], Vec::new())
+    }
+
+    pub fn change_vote(
+        ctx: Context<ChangeVote>,
+        selected_options: Vec<u8>, // Same shape as the question's cast instruction; empty for quadratic
+        votes: Vec<u16>,           // Quadratic questions only: votes per option
+    ) -> Result<()> {
+        let voting_question = &mut ctx.accounts.voting_question;
+        let vote = &mut ctx.accounts.vote;
+        let now = Clock::get()?.unix_timestamp;
+        ensure_vote_editable(voting_question, &ctx.accounts.community, vote, now)?;
+        let selected_options: Vec<u8> = match voting_question.ballot_type {
+            BallotType::Quadratic { .. } => votes
+                .iter()
+                .enumerate()
+                .filter(|(_, &count)| count > 0)
+                .map(|(index, _)| index as u8)
+                .collect(),
+            _ if !votes.is_empty() => return err!(VoteChangeError::UnexpectedAllocation),
+            _ => selected_options,
+        };
+        validate_selection(voting_question, &selected_options, &votes)?;
+
+        // Swap the old contributions for the new ones; the ballot count and weight are unchanged.
+        // Weight carried for delegators moves with the delegate's new choice.
+        let removed = ballot_contributions(
+            voting_question.ballot_type,
+            &vote.selected_options,
+            &vote.allocation,
+            vote.weight,
+        )?;
+        adjust_tally(voting_question, removed, 0, 0, false)?;
+        let added =
+            ballot_contributions(voting_question.ballot_type, &selected_options, &votes, vote.weight)?;
+        adjust_tally(voting_question, added, 0, 0, true)?;
+
+        let previous_options = std::mem::replace(&mut vote.selected_options, selected_options);
+        vote.selected_option = vote.selected_options[0];
+        vote.allocation = votes;
+        vote.voted_at = now;
+        emit!(VoteChanged {
+            question: vote.question,
+            voter: vote.voter,
+            previous_options,
+            selected_options: vote.selected_options.clone(),
+            allocation: vote.allocation.clone(),
+            weight: vote.weight,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn retract_vote(ctx: Context<RetractVote>) -> Result<()> {
+        let voting_question = &mut ctx.accounts.voting_question;
+        let vote = &ctx.accounts.vote;
+        let now = Clock::get()?.unix_timestamp;
+        ensure_vote_editable(voting_question, &ctx.accounts.community, vote, now)?;
+        // Delegators' markers point at this ballot, so it has to stay while it carries their weight
+        if vote.delegated_weight > 0 {
+            return err!(VoteChangeError::HoldsDelegatedVotes);
+        }
+        let contributions = ballot_contributions(
+            voting_question.ballot_type,
+            &vote.selected_options,
+            &vote.allocation,
+            vote.weight,
+        )?;
+        adjust_tally(voting_question, contributions, 1, vote.weight, false)?;
+        emit!(VoteRetracted {
+            question: vote.question,
+            voter: vote.voter,
+            weight: vote.weight,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn commit_vote(
+        ctx: Context<CastVote>,
+        commitment: [u8; 32], // sha256(question, voter, selected_option, salt)
+    ) -> Result<()> {
+        if ctx.accounts.voting_question.reveal_deadline.is_none() {
+            return err!(CommitRevealError::NotSecretBallot);
+        }
+        let now = Clock::get()?.unix_timestamp;
+        let weight = open_ballot(ctx.accounts, now)?;
+        let voting_question = &mut ctx.accounts.voting_question;
+        voting_question.total_commits = voting_question
+            .total_commits
+            .checked_add(1)
+            .ok_or(VotingQuestionError::TallyOverflow)?;
+        let vote = &mut ctx.accounts.vote;
+        vote.version = VOTE_VERSION;
+        vote.question = voting_question.key();
+        vote.voter = ctx.accounts.voter.key();
+        vote.selected_option = 0;
+        vote.selected_options = Vec::new();
+        vote.voted_at = now;
+        vote.tallied_round = 0;
+        vote.weight = weight;
+        vote.allocation = Vec::new();
+        vote.delegated_to = None;
+        vote.delegated_weight = 0;
+        vote.commitment = Some(commitment);
+        vote.revealed = false;
+        emit!(VoteCommitted {
+            question: vote.question,
+            voter: vote.voter,
+            commitment,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn reveal_vote(
+        ctx: Context<RevealVote>,
+        selected_option: u8,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        let question_key = ctx.accounts.voting_question.key();
+        let voting_question = &mut ctx.accounts.voting_question;
+        let vote = &mut ctx.accounts.vote;
+        let now = Clock::get()?.unix_timestamp;
+        reveal_ballot(voting_question, question_key, vote, selected_option, &salt, now)?;
+        emit!(VoteRevealed {
+            question: vote.question,
+            voter: vote.voter,
+            selected_option,
+            weight: vote.weight,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn cast_multiple_choice_vote<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CastVote<'info>>,
+        selected_options: Vec<u8>,
+    ) -> Result<()> {
+        if !matches!(ctx.accounts.voting_question.ballot_type, BallotType::MultipleChoice { .. }) {
+            return err!(VotingQuestionError::WrongBallotType);
+        }
+        record_ballot(ctx.accounts, ctx.remaining_accounts, selected_options, Vec::new())
+    }
+
+    pub fn cast_ranked_vote<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CastVote<'info>>,
+        ranking: Vec<u8>, // Option indices, most preferred first
+    ) -> Result<()> {
+        if ctx.accounts.voting_question.ballot_type != BallotType::RankedChoice {
+            return err!(VotingQuestionError::WrongBallotType);
+        }
+        record_ballot(ctx.accounts, ctx.remaining_accounts, ranking, Vec::new())
+    }
+
+    pub fn cast_quadratic_vote<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CastVote<'info>>,
+        votes: Vec<u16>, // Votes per option, costing votes^2 voice credits each
+    ) -> Result<()> {
+        if !matches!(ctx.accounts.voting_question.ballot_type, BallotType::Quadratic { .. }) {
+            return err!(VotingQuestionError::WrongBallotType);
+        }
+        let selected_options = votes
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(index, _)| index as u8)
+            .collect();
+        record_ballot(ctx.accounts, ctx.remaining_accounts, selected_options, votes)
+    }
+
+    pub fn close_voting_question(
+        ctx: Context<CloseVotingQuestion>,
+    ) -> Result<()> {
+        let voting_question = &mut ctx.accounts.voting_question;
+        let now = Clock::get()?.unix_timestamp;
+        ensure_question_closable(voting_question, &ctx.accounts.community, now)?;
+        voting_question.is_active = false;
+        emit!(VotingQuestionClosed {
+            question: ctx.accounts.voting_question.key(),
+            closer: ctx.accounts.closer.key(),
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // Close a question before its deadline. The result records the tally so far with a
+    // Cancelled outcome, which also stops it being finalized.
+    pub fn cancel_voting_question(
+        ctx: Context<CancelVotingQuestion>,
+        reason: String,
+    ) -> Result<()> {
+        let voting_question = &mut ctx.accounts.voting_question;
+        let community = &ctx.accounts.community;
+        let authority = ctx.accounts.authority.key();
+        let now = Clock::get()?.unix_timestamp;
+        let permissions = permissions_of(community, community.key(), authority, ctx.accounts.authority_member.as_deref());
+        ensure_question_cancellable(voting_question, community, authority, permissions, &reason, now)?;
+
+        let result = &mut ctx.accounts.result;
+        result.question = voting_question.key();
+        result.community = voting_question.community;
+        result.winning_options = Vec::new();
+        result.vote_counts = voting_question.vote_counts.clone();
+        result.total_ballots = voting_question.total_ballots;
+        result.total_weight = voting_question.total_weight;
+        result.eligible_voters = voting_question.eligible_voters;
+        result.turnout_bps = turnout_bps(voting_question.total_ballots, voting_question.eligible_voters);
+        result.is_tie = false;
+        result.outcome = QuestionOutcome::Cancelled;
+        result.finalized_at = now;
+        result.runoff_rounds = Vec::new();
+        result.unrevealed_commits = voting_question
+            .total_commits
+            .saturating_sub(voting_question.total_ballots);
+
+        voting_question.is_active = false;
+        voting_question.is_finalized = true;
+        emit!(VotingQuestionCancelled {
+            question: result.question,
+            result: result.key(),
+            cancelled_by: authority,
+            reason,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn finalize_question(
+        ctx: Context<FinalizeQuestion>,
+    ) -> Result<()> {
+        let voting_question = &mut ctx.accounts.voting_question;
+        let now = Clock::get()?.unix_timestamp;
+        // Secret ballots stay open for reveals until the reveal deadline
+        if now <= voting_question.reveal_deadline.unwrap_or(voting_question.deadline) {
+            return err!(FinalizationError::VotingNotEnded);
+        }
+        if voting_question.is_finalized {
+            return err!(FinalizationError::AlreadyFinalized);
+        }
+        // Ranked-choice questions take their result from the completed runoff
+        let (winners, vote_counts, runoff_rounds) =
+            if voting_question.ballot_type == BallotType::RankedChoice {
+                let runoff = match &ctx.accounts.runoff {
+                    Some(runoff) if runoff.question == voting_question.key() => runoff,
+                    _ => return err!(RunoffError::RunoffIncomplete),
+                };
+                if !runoff.is_complete {
+                    return err!(RunoffError::RunoffIncomplete);
+                }
+                let final_counts = runoff
+                    .rounds
+                    .last()
+                    .map(|round| round.vote_counts.clone())
+                    .unwrap_or_else(|| voting_question.vote_counts.clone());
+                (runoff.winning_options.clone(), final_counts, runoff.rounds.clone())
+            } else {
+                (
+                    winning_options(&voting_question.vote_counts),
+                    voting_question.vote_counts.clone(),
+                    Vec::new(),
+                )
+            };
+        let turnout = turnout_bps(voting_question.total_ballots, voting_question.eligible_voters);
+        let outcome = question_outcome(voting_question, &vote_counts, &winners);
+
+        let result = &mut ctx.accounts.result;
+        result.question = voting_question.key();
+        result.community = voting_question.community;
+        result.is_tie = winners.len() > 1;
+        result.winning_options = winners;
+        result.vote_counts = vote_counts;
+        result.total_ballots = voting_question.total_ballots;
+        result.total_weight = voting_question.total_weight;
+        result.eligible_voters = voting_question.eligible_voters;
+        result.turnout_bps = turnout;
+        result.outcome = outcome;
+        result.finalized_at = now;
+        result.runoff_rounds = runoff_rounds;
+        result.unrevealed_commits = voting_question
+            .total_commits
+            .saturating_sub(voting_question.total_ballots);
+
+        voting_question.is_active = false;
+        voting_question.is_finalized = true;
+        // The runoff crank state is no longer needed once its rounds are on the result
+        if let Some(runoff) = &ctx.accounts.runoff {
+            if runoff.question != voting_question.key() {
+                return err!(RunoffError::WrongQuestion);
+            }
+            let runoff_payer = match &ctx.accounts.runoff_payer {
+                Some(runoff_payer) if runoff_payer.key() == runoff.payer => runoff_payer,
+                _ => return err!(RunoffError::WrongRunoffPayer),
+            };
+            runoff.close(runoff_payer.to_account_info())?;
+        }
+        emit!(QuestionFinalized {
+            question: result.question,
+            result: result.key(),
+            winning_options: result.winning_options.clone(),
+            total_ballots: result.total_ballots,
+            turnout_bps: result.turnout_bps,
+            is_tie: result.is_tie,
+            outcome,
+            unrevealed_commits: result.unrevealed_commits,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn begin_runoff(
+        ctx: Context<BeginRunoff>,
+    ) -> Result<()> {
+        let voting_question = &ctx.accounts.voting_question;
+        let now = Clock::get()?.unix_timestamp;
+        if voting_question.ballot_type != BallotType::RankedChoice {
+            return err!(RunoffError::NotRankedChoice);
+        }
+        if now <= voting_question.deadline {
+            return err!(FinalizationError::VotingNotEnded);
+        }
+        let runoff = &mut ctx.accounts.runoff;
+        runoff.question = voting_question.key();
+        runoff.payer = ctx.accounts.payer.key();
+        start_runoff(runoff, voting_question);
+        Ok(())
+    }
+
+    pub fn process_runoff_ballots<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ProcessRunoffBallots<'info>>,
+    ) -> Result<()> {
+        let voting_question = &ctx.accounts.voting_question;
+        let runoff = &mut ctx.accounts.runoff;
+        if runoff.is_complete {
+            return err!(RunoffError::RunoffComplete);
+        }
+        for account_info in ctx.remaining_accounts.iter() {
+            let mut vote: Account<Vote> = Account::try_from(account_info)?;
+            if vote.question != voting_question.key() {
+                return err!(RunoffError::WrongQuestion);
+            }
+            if vote.tallied_round != runoff.round {
+                return err!(RunoffError::BallotAlreadyCounted);
+            }
+            let round = runoff.round;
+            vote.tallied_round = round + 1;
+            vote.exit(&crate::ID)?;
+
+            if count_runoff_ballot(runoff, voting_question, &vote.selected_options, vote.weight)? {
+                let closed = runoff.rounds.last().cloned().unwrap_or_default();
+                emit!(RunoffRoundCompleted {
+                    question: voting_question.key(),
+                    round,
+                    vote_counts: closed.vote_counts,
+                    eliminated: closed.eliminated,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+                // Ballots for the next round must come in a later batch
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn deposit_tokens(
+        ctx: Context<DepositTokens>,
+        amount: u64,
+    ) -> Result<()> {
+        let mint = match ctx.accounts.community.config.vote_weighting {
+            VoteWeighting::LockedTokens { mint } => mint,
+            _ => return err!(EscrowError::NotLockedTokens),
+        };
+        if ctx.accounts.mint.key() != mint {
+            return err!(EscrowError::WrongMint);
+        }
+        lock_tokens(&mut ctx.accounts.escrow, mint, amount)?;
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        let escrow = &mut ctx.accounts.escrow;
+        escrow.community = ctx.accounts.community.key();
+        escrow.owner = ctx.accounts.owner.key();
+        let now = Clock::get()?.unix_timestamp;
+        emit!(TokensDeposited {
+            community: escrow.community,
+            owner: escrow.owner,
+            amount,
+            total_locked: escrow.amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn withdraw_tokens(
+        ctx: Context<WithdrawTokens>,
+        amount: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        unlock_tokens(&mut ctx.accounts.escrow, amount, now)?;
+        let community_key = ctx.accounts.community.key();
+        let mint_key = ctx.accounts.mint.key();
+        let seeds: &[&[u8]] = &[
+            b"vault",
+            community_key.as_ref(),
+            mint_key.as_ref(),
+            &[ctx.bumps.vault],
+        ];
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: ctx.accounts.vault.to_account_info(),
+                },
+                &[seeds],
+            ),
+            amount,
+        )?;
+        emit!(TokensWithdrawn {
+            community: community_key,
+            owner: ctx.accounts.escrow.owner,
+            amount,
+            total_locked: ctx.accounts.escrow.amount,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn delegate_vote(
+        ctx: Context<DelegateVote>,
+        question: Option<Pubkey>, // Limit the delegation to one question, or None for all
+    ) -> Result<()> {
+        let community = &ctx.accounts.community;
+        let delegator_member = &mut ctx.accounts.delegator_member;
+        let delegate_member = &mut ctx.accounts.delegate_member;
+        ensure_community_active(community)?;
+        link_delegation(delegator_member, delegate_member)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let delegation = &mut ctx.accounts.delegation;
+        delegation.community = community.key();
+        delegation.delegator = delegator_member.wallet;
+        delegation.delegate = delegate_member.wallet;
+        delegation.question = question;
+        delegation.created_at = now;
+        emit!(VoteDelegated {
+            community: delegation.community,
+            delegator: delegation.delegator,
+            delegate: delegation.delegate,
+            question,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn revoke_delegation(
+        ctx: Context<RevokeDelegation>,
+    ) -> Result<()> {
+        let delegation = &ctx.accounts.delegation;
+        unlink_delegation(&mut ctx.accounts.delegator_member, &mut ctx.accounts.delegate_member);
+        let now = Clock::get()?.unix_timestamp;
+        emit!(DelegationRevoked {
+            community: delegation.community,
+            delegator: delegation.delegator,
+            delegate: delegation.delegate,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn claim_delegated_votes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimDelegatedVotes<'info>>,
+    ) -> Result<()> {
+        let voting_question = &mut ctx.accounts.voting_question;
+        let vote = &mut ctx.accounts.vote;
+        let now = Clock::get()?.unix_timestamp;
+        if !voting_question.is_active {
+            return err!(VotingQuestionError::NotActive);
+        }
+        if now > voting_question.deadline {
+            return err!(VotingQuestionError::DeadlinePassed);
+        }
+        claim_delegations(
+            voting_question,
+            vote,
+            &ctx.accounts.voter.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.remaining_accounts,
+            now,
+        )
+    }
+
+    pub fn remove_member(
+        ctx: Context<RemoveMember>,
+    ) -> Result<()> {
+        let admin = ctx.accounts.authority.key();
+        require_permission(
+            &ctx.accounts.community,
+            admin,
+            ctx.accounts.authority_member.as_deref(),
+            PERMISSION_REMOVE_MEMBERS,
+        )?;
+        ensure_admin_may_act_alone(&ctx.accounts.community, &admin)?;
+        let community_key = ctx.accounts.community.key();
+        let now = Clock::get()?.unix_timestamp;
+        apply_remove_member(&mut ctx.accounts.community, community_key, &mut ctx.accounts.member, admin, now)
+    }
+
+    // Rent reclamation. Votes and finalized questions can be closed by anyone once the
+    // result is persisted; rent always goes back to whoever paid for the account. A voter
+    // overriding a delegate's marker refunds the delegate, taking over the marker's rent.
+
+    pub fn close_vote(ctx: Context<CloseVote>) -> Result<()> {
+        emit!(AccountClosed {
+            account: ctx.accounts.vote.key(),
+            rent_recipient: ctx.accounts.rent_recipient.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn close_finalized_question(ctx: Context<CloseFinalizedQuestion>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ensure_action_settled(&ctx.accounts.voting_question, &ctx.accounts.result, now)?;
+        emit!(AccountClosed {
+            account: ctx.accounts.voting_question.key(),
+            rent_recipient: ctx.accounts.creator.key(),
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // A member withdraws a pending request or leaves, closing their Member account
+    pub fn leave_community(ctx: Context<LeaveCommunity>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let member = &ctx.accounts.member;
+        ensure_member_closable(member)?;
+        if member.status == MemberStatus::Approved {
+            community.member_count = community.member_count.saturating_sub(1);
+        }
+        emit!(MemberLeft {
+            community: community.key(),
+            member_wallet: member.wallet,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Close the account of a rejected or removed member; the member or the admin may do this
+    pub fn close_member(ctx: Context<CloseMember>) -> Result<()> {
+        let member = &ctx.accounts.member;
+        let closer = ctx.accounts.closer.key();
+        let community = &ctx.accounts.community;
+        let permissions = permissions_of(community, community.key(), closer, ctx.accounts.closer_member.as_deref());
+        ensure_member_close_allowed(member, closer, permissions)?;
+        emit!(AccountClosed {
+            account: member.key(),
+            rent_recipient: member.wallet,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn dissolve_community(
+        ctx: Context<DissolveCommunity>,
+    ) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let admin = ctx.accounts.admin.key();
+        if !is_admin(community, &admin) {
+            return err!(AdminActionError::NotAdmin);
+        }
+        ensure_admin_may_act_alone(community, &admin)?;
+        let community_key = community.key();
+        apply_dissolve(community, community_key, admin, Clock::get()?.unix_timestamp)
+    }
+
+    pub fn pause_community(ctx: Context<SetCommunityStatus>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        match community.status {
+            CommunityStatus::Active => {}
+            CommunityStatus::Paused => return err!(AdminActionError::AlreadyPaused),
+            CommunityStatus::Dissolved => return err!(AdminActionError::CommunityDissolved),
+        }
+        community.status = CommunityStatus::Paused;
+        emit!(CommunityPaused {
+            community: community.key(),
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn resume_community(ctx: Context<SetCommunityStatus>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        if community.status != CommunityStatus::Paused {
+            return err!(AdminActionError::NotPaused);
+        }
+        community.status = CommunityStatus::Active;
+        emit!(CommunityResumed {
+            community: community.key(),
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Migrations: rewrite an account from an older schema version into the current layout.
+    // Anyone may run them; the caller pays for any extra rent.
+
+    pub fn migrate_community(ctx: Context<MigrateAccount>) -> Result<()> {
+        let info = ctx.accounts.account.to_account_info();
+        let discriminator = <Community as Discriminator>::DISCRIMINATOR;
+        let from_version = stored_version(&info.try_borrow_data()?, discriminator, COMMUNITY_V0_SPACE)?;
+        let community = migrated_community(from_version, &info.try_borrow_data()?[8..])?;
+        rewrite_account(
+            &info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            COMMUNITY_SPACE,
+            &community,
+        )?;
+        emit!(AccountMigrated {
+            account: info.key(),
+            from_version,
+            to_version: COMMUNITY_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn migrate_member(ctx: Context<MigrateAccount>) -> Result<()> {
+        let info = ctx.accounts.account.to_account_info();
+        let discriminator = <Member as Discriminator>::DISCRIMINATOR;
+        let from_version = stored_version(&info.try_borrow_data()?, discriminator, MEMBER_V0_SPACE)?;
+        let member = migrated_member(from_version, &info.try_borrow_data()?[8..])?;
+        rewrite_account(
+            &info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            MEMBER_SPACE,
+            &member,
+        )?;
+        emit!(AccountMigrated {
+            account: info.key(),
+            from_version,
+            to_version: MEMBER_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn migrate_voting_question(ctx: Context<MigrateVotingQuestion>) -> Result<()> {
+        let community_info = ctx.accounts.community.to_account_info();
+        let discriminator = <Community as Discriminator>::DISCRIMINATOR;
+        if stored_version(&community_info.try_borrow_data()?, discriminator, COMMUNITY_V0_SPACE)? != COMMUNITY_VERSION {
+            return err!(MigrationError::ParentNotMigrated);
+        }
+        let info = ctx.accounts.account.to_account_info();
+        let discriminator = <VotingQuestion as Discriminator>::DISCRIMINATOR;
+        let from_version = stored_version(&info.try_borrow_data()?, discriminator, QUESTION_V0_SPACE)?;
+        let voting_question = migrated_voting_question(
+            from_version,
+            &info.try_borrow_data()?[8..],
+            ctx.accounts.community.member_count,
+        )?;
+        if voting_question.community != community_info.key() {
+            return err!(MigrationError::WrongParent);
+        }
+        rewrite_account(
+            &info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            QUESTION_SPACE,
+            &voting_question,
+        )?;
+        emit!(AccountMigrated {
+            account: info.key(),
+            from_version,
+            to_version: QUESTION_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn migrate_vote(ctx: Context<MigrateVote>) -> Result<()> {
+        let question_info = ctx.accounts.voting_question.to_account_info();
+        let discriminator = <VotingQuestion as Discriminator>::DISCRIMINATOR;
+        if stored_version(&question_info.try_borrow_data()?, discriminator, QUESTION_V0_SPACE)? != QUESTION_VERSION {
+            return err!(MigrationError::ParentNotMigrated);
+        }
+        let info = ctx.accounts.account.to_account_info();
+        let discriminator = <Vote as Discriminator>::DISCRIMINATOR;
+        let from_version = stored_version(&info.try_borrow_data()?, discriminator, VOTE_V0_SPACE)?;
+        let vote = migrated_vote(from_version, &info.try_borrow_data()?[8..])?;
+        if vote.question != question_info.key() {
+            return err!(MigrationError::WrongParent);
+        }
+        // Old questions kept no tally, so each vote is counted as it is upgraded
+        recount_migrated_vote(&mut ctx.accounts.voting_question, &vote)?;
+        rewrite_account(
+            &info,
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            VOTE_SPACE,
+            &vote,
+        )?;
+        emit!(AccountMigrated {
+            account: info.key(),
+            from_version,
+            to_version: VOTE_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn update_community_config(
+        ctx: Context<UpdateCommunityConfig>,
+        new_config: CommunityConfig,
+    ) -> Result<()> {
+        require_permission(
+            &ctx.accounts.community,
+            ctx.accounts.authority.key(),
+            ctx.accounts.authority_member.as_deref(),
+            PERMISSION_EDIT_CONFIG,
+        )?;
+        ensure_admin_may_act_alone(&ctx.accounts.community, ctx.accounts.authority.key)?;
+        // Validation for the new config
+        validate_config(&new_config)?;
+
+        let community = &mut ctx.accounts.community;
+        community.config = new_config;
+        
+        msg!("Community config updated by {}", ctx.accounts.authority.key);
+        Ok(())
+    }
+
+    pub fn change_member_role(
+        ctx: Context<ChangeMemberRole>,
+        new_role: MemberRole,
+    ) -> Result<()> {
+        let admin = ctx.accounts.authority.key();
+        let community = &ctx.accounts.community;
+        ensure_no_admin_threshold(community)?;
+        let held = permissions_of(community, community.key(), admin, ctx.accounts.authority_member.as_deref());
+        authorize_grant(held, role_permissions(new_role))?;
+        let now = Clock::get()?.unix_timestamp;
+        apply_role_change(community.key(), &mut ctx.accounts.member, new_role, admin, now)
+    }
+
+    // Fine-tune a member's permissions without changing their role
+    pub fn set_member_permissions(
+        ctx: Context<ChangeMemberRole>,
+        permissions: u16, // PERMISSION_* bits
+    ) -> Result<()> {
+        if permissions & !ALL_PERMISSIONS != 0 {
+            return err!(PermissionError::InvalidPermissions);
+        }
+        let admin = ctx.accounts.authority.key();
+        let community = &ctx.accounts.community;
+        ensure_no_admin_threshold(community)?;
+        let held = permissions_of(community, community.key(), admin, ctx.accounts.authority_member.as_deref());
+        authorize_grant(held, permissions)?;
+        let now = Clock::get()?.unix_timestamp;
+        apply_permissions_change(community.key(), &mut ctx.accounts.member, permissions, admin, now)
+    }
+
+    // Add an approved member to the community's admin set
+    pub fn add_admin(ctx: Context<AddAdmin>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        ensure_no_admin_threshold(community)?;
+        let community_key = community.key();
+        let now = Clock::get()?.unix_timestamp;
+        apply_add_admin(community, community_key, &mut ctx.accounts.member, ctx.accounts.admin.key(), now)
+    }
+
+    // Remove a wallet from the admin set; admins may also remove themselves
+    pub fn remove_admin(ctx: Context<RemoveAdmin>, wallet: Pubkey) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        ensure_no_admin_threshold(community)?;
+        let community_key = community.key();
+        let now = Clock::get()?.unix_timestamp;
+        apply_remove_admin(community, community_key, wallet, ctx.accounts.admin.key(), now)?;
+        // Demote their Member account too, so it doesn't keep admin permissions
+        set_member_role(&ctx.accounts.member, MemberRole::Member)
+    }
+
+    // Offer the signer's admin seat to `new_admin`; it only moves once they accept
+    pub fn propose_admin_transfer(
+        ctx: Context<ManageAdminTransfer>,
+        new_admin: Pubkey,
+        expires_at: Option<i64>, // None: the offer stands until accepted or cancelled
+    ) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let from = ctx.accounts.authority.key();
+        let now = Clock::get()?.unix_timestamp;
+        propose_transfer(community, from, new_admin, expires_at, now)?;
+        emit!(AdminTransferProposed {
+            community: community.key(),
+            from,
+            to: new_admin,
+            expires_at,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn accept_admin_transfer(ctx: Context<AcceptAdminTransfer>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let new_admin = ctx.accounts.new_admin.key();
+        let now = Clock::get()?.unix_timestamp;
+        let transfer = accept_transfer(community, new_admin, now)?;
+        if ctx.accounts.previous_member.key() != member_address(&community.key(), &transfer.from) {
+            return err!(AdminTransferError::WrongMemberAccount);
+        }
+        set_member_role(&ctx.accounts.previous_member, MemberRole::Member)?;
+        set_member_role(&ctx.accounts.new_member, MemberRole::Admin)?;
+        emit!(AdminTransferAccepted {
+            community: community.key(),
+            from: transfer.from,
+            to: new_admin,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    // Withdraw a pending transfer: any admin may cancel it, and the proposed wallet may decline it
+    pub fn cancel_admin_transfer(ctx: Context<ManageAdminTransfer>) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let authority = ctx.accounts.authority.key();
+        let Some(transfer) = community.pending_admin.clone() else {
+            return err!(AdminTransferError::NoPendingTransfer);
+        };
+        if authority != transfer.to && !is_admin(community, &authority) {
+            return err!(AdminTransferError::NotAuthorized);
+        }
+        community.pending_admin = None;
+        emit!(AdminTransferCancelled {
+            community: community.key(),
+            from: transfer.from,
+            to: transfer.to,
+            cancelled_by: authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // M-of-N admin actions: an admin proposes, other admins approve, and once
+    // `admin_threshold` current admins have approved anyone may execute it.
+
+    pub fn create_admin_proposal(
+        ctx: Context<CreateAdminProposal>,
+        action: CommunityAction,
+        expires_at: i64,
+    ) -> Result<()> {
+        let community = &mut ctx.accounts.community;
+        let proposer = ctx.accounts.proposer.key();
+        let now = Clock::get()?.unix_timestamp;
+        if !is_admin(community, &proposer) {
+            return err!(AdminActionError::NotAdmin);
+        }
+        if community.status == CommunityStatus::Dissolved {
+            return err!(AdminActionError::CommunityDissolved);
+        }
+        if expires_at <= now {
+            return err!(MultisigError::InvalidExpiry);
+        }
+        validate_community_action(community, &action)?;
+        let index = next_admin_proposal_index(community)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.community = community.key();
+        proposal.index = index;
+        proposal.proposer = proposer;
+        proposal.action = action.clone();
+        // Proposing counts as the proposer's approval
+        proposal.approvals = vec![proposer];
+        proposal.created_at = now;
+        proposal.expires_at = expires_at;
+        emit!(AdminProposalCreated {
+            community: community.key(),
+            proposal: proposal.key(),
+            index,
+            proposer,
+            action,
+            expires_at,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn approve_admin_proposal(ctx: Context<ApproveAdminProposal>) -> Result<()> {
+        let community = &ctx.accounts.community;
+        let proposal = &mut ctx.accounts.proposal;
+        let admin = ctx.accounts.admin.key();
+        let now = Clock::get()?.unix_timestamp;
+        record_approval(community, proposal, admin, now)?;
+        emit!(AdminProposalApproved {
+            community: community.key(),
+            proposal: proposal.key(),
+            approver: admin,
+            approvals: proposal.approvals.len() as u8,
+            threshold: community.admin_threshold,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn execute_admin_proposal(ctx: Context<ExecuteAdminProposal>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        let executor = ctx.accounts.executor.key();
+        let now = Clock::get()?.unix_timestamp;
+        ensure_proposal_executable(&ctx.accounts.community, proposal, now)?;
+        let community_key = ctx.accounts.community.key();
+        let admin_member = unseated_admin_member(&proposal.action, ctx.accounts.admin_member.as_ref(), &community_key)?;
+        execute_community_action(
+            &mut ctx.accounts.community,
+            community_key,
+            ctx.accounts.member.as_deref_mut(),
+            &proposal.action,
+            executor,
+            now,
+        )?;
+        if let Some(info) = admin_member {
+            set_member_role(&info, MemberRole::Member)?;
+        }
+        emit!(AdminProposalExecuted {
+            community: ctx.accounts.community.key(),
+            proposal: proposal.key(),
+            executed_by: executor,
+            timestamp: now,
+        });
+        Ok(())
+    }
+
+    pub fn cancel_admin_proposal(ctx: Context<CancelAdminProposal>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let expired = Clock::get()?.unix_timestamp > ctx.accounts.proposal.expires_at;
+        if !expired && !is_admin(&ctx.accounts.community, &authority) {
+            return err!(MultisigError::NotAuthorized);
+        }
+        emit!(AdminProposalCancelled {
+            community: ctx.accounts.community.key(),
+            proposal: ctx.accounts.proposal.key(),
+            cancelled_by: authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    // Apply the action of a governance question that passed in its favour. Permissionless:
+    // the community's vote is the authorization, so the admin threshold doesn't apply.
+    pub fn execute_question_action(ctx: Context<ExecuteQuestionAction>) -> Result<()> {
+        let executor = ctx.accounts.executor.key();
+        let now = Clock::get()?.unix_timestamp;
+        let action = executable_action(&ctx.accounts.voting_question, &ctx.accounts.result, now)?;
+        let community_key = ctx.accounts.community.key();
+        let admin_member = unseated_admin_member(&action, ctx.accounts.admin_member.as_ref(), &community_key)?;
+        execute_community_action(
+            &mut ctx.accounts.community,
+            community_key,
+            ctx.accounts.member.as_deref_mut(),
+            &action,
+            executor,
+            now,
+        )?;
+        if let Some(info) = admin_member {
+            set_member_role(&info, MemberRole::Member)?;
+        }
+        ctx.accounts.voting_question.action_executed = true;
+        emit!(GovernanceActionExecuted {
+            community: community_key,
+            question: ctx.accounts.voting_question.key(),
+            executed_by: executor,
+            timestamp: now,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(options: usize) -> VotingQuestion {
+        VotingQuestion {
+            version: QUESTION_VERSION,
+            community: Pubkey::new_unique(),
+            creator: Pubkey::new_unique(),
+            question: "Q".to_string(),
+            options: vec!["A".to_string(); options],
+            deadline: 0,
+            created_at: 0,
+            is_active: true,
+            vote_counts: vec![0; options],
+            total_ballots: 0,
+            eligible_voters: 10,
+            is_finalized: false,
+            quorum: QuorumRule::None,
+            pass_threshold: PassThreshold::SimpleMajority,
+            ballot_type: BallotType::SingleChoice,
+            vote_weighting: VoteWeighting::OnePerMember,
+            total_weight: 0,
+            reveal_deadline: None,
+            total_commits: 0,
+            index: 0,
+            starts_at: None,
+            action: None,
+            action_executed: false,
+        }
+    }
+
+    // Tally a ballot the way cast_vote does
+    fn cast(voting_question: &mut VotingQuestion, selected_options: &[u8], weight: u64) -> Result<()> {
+        let contributions = ballot_contributions(voting_question.ballot_type, selected_options, &[], weight)?;
+        adjust_tally(voting_question, contributions, 1, weight, true)
+    }
+
+    #[test]
+    fn ballots_update_the_running_tally() {
+        let mut voting_question = question(3);
+        cast(&mut voting_question, &[1], 1).unwrap();
+        cast(&mut voting_question, &[1], 1).unwrap();
+        cast(&mut voting_question, &[2], 1).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![0, 2, 1]);
+        assert_eq!(voting_question.total_ballots, 3);
+    }
+
+    #[test]
+    fn tally_rejects_unknown_options_and_overflow() {
+        let mut voting_question = question(2);
+        assert_eq!(
+            cast(&mut voting_question, &[2], 1).unwrap_err(),
+            VotingQuestionError::InvalidOption.into()
+        );
+        voting_question.vote_counts[0] = u64::MAX;
+        assert_eq!(
+            cast(&mut voting_question, &[0], 1).unwrap_err(),
+            VotingQuestionError::TallyOverflow.into()
+        );
+        assert_eq!(voting_question.total_ballots, 0);
+    }
+
+    #[test]
+    fn winners_share_the_highest_count() {
+        assert_eq!(winning_options(&[1, 5, 2]), vec![1]);
+        assert_eq!(winning_options(&[4, 1, 4]), vec![0, 2]);
+        // No ballots means no winner rather than a tie between every option
+        assert!(winning_options(&[0, 0, 0]).is_empty());
+        assert!(winning_options(&[]).is_empty());
+    }
+
+    #[test]
+    fn turnout_is_a_capped_share_of_eligible_voters() {
+        assert_eq!(turnout_bps(1, 2), 5_000);
+        assert_eq!(turnout_bps(1, 3), 3_333);
+        assert_eq!(turnout_bps(0, 10), 0);
+        assert_eq!(turnout_bps(5, 0), 0);
+        // Members approved after the question was created can push turnout past 100%
+        assert_eq!(turnout_bps(12, 10), 10_000);
+        assert_eq!(turnout_bps(u64::MAX, 1), 10_000);
+    }
+
+    // A question holding these final counts
+    fn tallied(vote_counts: &[u64]) -> VotingQuestion {
+        let mut voting_question = question(vote_counts.len());
+        voting_question.vote_counts = vote_counts.to_vec();
+        voting_question.total_ballots = vote_counts.iter().sum();
+        voting_question.total_weight = voting_question.total_ballots;
+        voting_question
+    }
+
+    fn config(quorum: QuorumRule, pass_threshold: PassThreshold) -> CommunityConfig {
+        CommunityConfig {
+            voting_period: 3600,
+            min_voting_period: 0,
+            max_options: 4,
+            quorum,
+            pass_threshold,
+            vote_weighting: VoteWeighting::OnePerMember,
+        }
+    }
+
+    #[test]
+    fn config_rules_must_be_meaningful() {
+        assert!(validate_config(&config(QuorumRule::None, PassThreshold::SimpleMajority)).is_ok());
+        assert!(validate_config(&config(
+            QuorumRule::PercentOfMembers { bps: 10_000 },
+            PassThreshold::Supermajority { bps: 5_001 },
+        ))
+        .is_ok());
+        for quorum in [
+            QuorumRule::Absolute { min_ballots: 0 },
+            QuorumRule::PercentOfMembers { bps: 0 },
+            QuorumRule::PercentOfMembers { bps: 10_001 },
+        ] {
+            assert_eq!(
+                validate_config(&config(quorum, PassThreshold::SimpleMajority)).unwrap_err(),
+                VotingError::InvalidQuorum.into()
+            );
+        }
+        for bps in [5_000, 10_001] {
+            assert_eq!(
+                validate_config(&config(QuorumRule::None, PassThreshold::Supermajority { bps })).unwrap_err(),
+                VotingError::InvalidPassThreshold.into()
+            );
+        }
+    }
+
+    #[test]
+    fn outcome_applies_absolute_quorum() {
+        let mut voting_question = tallied(&[2, 0]);
+        voting_question.quorum = QuorumRule::Absolute { min_ballots: 3 };
+        assert_eq!(question_outcome(&voting_question, &voting_question.vote_counts, &[0]), QuestionOutcome::QuorumNotMet);
+        let mut voting_question = tallied(&[3, 0]);
+        voting_question.quorum = QuorumRule::Absolute { min_ballots: 3 };
+        assert_eq!(question_outcome(&voting_question, &voting_question.vote_counts, &[0]), QuestionOutcome::Passed);
+    }
+
+    #[test]
+    fn outcome_applies_percent_of_members_quorum() {
+        // 25% of 10 eligible voters is 2.5 ballots, so 3 are needed
+        let mut voting_question = tallied(&[2, 0]);
+        voting_question.quorum = QuorumRule::PercentOfMembers { bps: 2_500 };
+        assert_eq!(question_outcome(&voting_question, &voting_question.vote_counts, &[0]), QuestionOutcome::QuorumNotMet);
+        let mut voting_question = tallied(&[3, 0]);
+        voting_question.quorum = QuorumRule::PercentOfMembers { bps: 2_500 };
+        assert_eq!(question_outcome(&voting_question, &voting_question.vote_counts, &[0]), QuestionOutcome::Passed);
+    }
+
+    #[test]
+    fn outcome_supermajority_passes_at_exactly_the_threshold() {
+        let mut voting_question = tallied(&[6, 4]);
+        voting_question.pass_threshold = PassThreshold::Supermajority { bps: 6_000 };
+        assert_eq!(question_outcome(&voting_question, &voting_question.vote_counts, &[0]), QuestionOutcome::Passed);
+        let mut voting_question = tallied(&[59, 41]);
+        voting_question.pass_threshold = PassThreshold::Supermajority { bps: 6_000 };
+        assert_eq!(question_outcome(&voting_question, &voting_question.vote_counts, &[0]), QuestionOutcome::Failed);
+        // Simple majority needs strictly more than half
+        assert_eq!(question_outcome(&tallied(&[5, 5, 0]), &[5, 5, 0], &[0]), QuestionOutcome::Failed);
+        assert_eq!(question_outcome(&tallied(&[6, 5, 0]), &[6, 5, 0], &[0]), QuestionOutcome::Passed);
+    }
+
+    #[test]
+    fn outcome_tie_fails() {
+        assert_eq!(question_outcome(&tallied(&[2, 2, 0]), &[2, 2, 0], &[0, 1]), QuestionOutcome::Failed);
+        // No ballots at all leaves no winner either
+        assert_eq!(question_outcome(&tallied(&[0, 0, 0]), &[0, 0, 0], &[]), QuestionOutcome::Failed);
+    }
+
+    #[test]
+    fn approval_ballots_count_once_per_selected_option() {
+        let mut voting_question = question(3);
+        cast(&mut voting_question, &[0, 2], 1).unwrap();
+        cast(&mut voting_question, &[2], 1).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![1, 0, 2]);
+        assert_eq!(voting_question.total_ballots, 2);
+    }
+
+    #[test]
+    fn selection_matches_ballot_type() {
+        let single = question(3);
+        assert!(validate_selection(&single, &[2], &[]).is_ok());
+        assert_eq!(
+            validate_selection(&single, &[0, 1], &[]).unwrap_err(),
+            VotingQuestionError::WrongBallotType.into()
+        );
+        assert_eq!(
+            validate_selection(&single, &[3], &[]).unwrap_err(),
+            VotingQuestionError::InvalidOption.into()
+        );
+
+        let mut multiple = question(4);
+        multiple.ballot_type = BallotType::MultipleChoice { min_selections: 2, max_selections: 3 };
+        assert!(validate_selection(&multiple, &[0, 3], &[]).is_ok());
+        assert!(validate_selection(&multiple, &[0, 1, 2], &[]).is_ok());
+        assert_eq!(
+            validate_selection(&multiple, &[0], &[]).unwrap_err(),
+            VotingQuestionError::InvalidSelectionCount.into()
+        );
+        assert_eq!(
+            validate_selection(&multiple, &[0, 1, 2, 3], &[]).unwrap_err(),
+            VotingQuestionError::InvalidSelectionCount.into()
+        );
+        assert_eq!(
+            validate_selection(&multiple, &[1, 1], &[]).unwrap_err(),
+            VotingQuestionError::DuplicateOption.into()
+        );
+    }
+
+    #[test]
+    fn ranked_ballots_must_order_every_option() {
+        let mut ranked = question(3);
+        ranked.ballot_type = BallotType::RankedChoice;
+        assert!(validate_selection(&ranked, &[2, 0, 1], &[]).is_ok());
+        assert_eq!(
+            validate_selection(&ranked, &[2, 0], &[]).unwrap_err(),
+            VotingQuestionError::InvalidSelectionCount.into()
+        );
+        assert_eq!(
+            validate_selection(&ranked, &[2, 0, 2], &[]).unwrap_err(),
+            VotingQuestionError::DuplicateOption.into()
+        );
+    }
+
+    // Count ranked ballots round by round, one batch per round, until the runoff completes
+    fn run_runoff(ballots: &[&[u8]], options: usize) -> RunoffTally {
+        let mut voting_question = question(options);
+        voting_question.ballot_type = BallotType::RankedChoice;
+        voting_question.total_ballots = ballots.len() as u64;
+        voting_question.total_weight = voting_question.total_ballots;
+        let mut runoff = RunoffTally {
+            question: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+            round: 0,
+            eliminated: Vec::new(),
+            round_counts: Vec::new(),
+            ballots_processed: 0,
+            rounds: Vec::new(),
+            winning_options: Vec::new(),
+            is_complete: false,
+        };
+        start_runoff(&mut runoff, &voting_question);
+        while !runoff.is_complete {
+            for &ranking in ballots {
+                if count_runoff_ballot(&mut runoff, &voting_question, ranking, 1).unwrap() {
+                    break;
+                }
+            }
+        }
+        runoff
+    }
+
+    #[test]
+    fn runoff_majority_wins_first_round() {
+        let runoff = run_runoff(&[&[0, 1, 2], &[0, 2, 1], &[0, 2, 1], &[1, 0, 2], &[2, 1, 0]], 3);
+        assert!(runoff.is_complete);
+        assert_eq!(runoff.winning_options, vec![0]);
+        assert_eq!(runoff.rounds.len(), 1);
+        assert_eq!(runoff.rounds[0].vote_counts, vec![3, 1, 1]);
+        assert!(runoff.rounds[0].eliminated.is_empty());
+    }
+
+    #[test]
+    fn runoff_eliminates_every_option_tied_for_last() {
+        let runoff = run_runoff(
+            &[
+                &[0, 1, 2, 3],
+                &[0, 1, 2, 3],
+                &[0, 1, 2, 3],
+                &[1, 0, 2, 3],
+                &[1, 0, 2, 3],
+                &[2, 1, 0, 3],
+                &[3, 1, 0, 2],
+            ],
+            4,
+        );
+        assert_eq!(runoff.rounds.len(), 2);
+        assert_eq!(runoff.rounds[0].vote_counts, vec![3, 2, 1, 1]);
+        assert_eq!(runoff.rounds[0].eliminated, vec![2, 3]);
+        // Both eliminated ballots transfer to option 1, which now holds a majority
+        assert_eq!(runoff.rounds[1].vote_counts, vec![3, 4, 0, 0]);
+        assert_eq!(runoff.eliminated, vec![2, 3]);
+        assert_eq!(runoff.winning_options, vec![1]);
+    }
+
+    #[test]
+    fn runoff_all_tied_round_ends_in_a_tie() {
+        let runoff = run_runoff(&[&[0, 1, 2], &[1, 2, 0], &[2, 0, 1]], 3);
+        assert!(runoff.is_complete);
+        assert_eq!(runoff.rounds.len(), 1);
+        assert!(runoff.rounds[0].eliminated.is_empty());
+        assert_eq!(runoff.winning_options, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn runoff_without_ballots_completes_without_winner() {
+        let runoff = run_runoff(&[], 3);
+        assert!(runoff.is_complete);
+        assert!(runoff.rounds.is_empty());
+        assert!(runoff.winning_options.is_empty());
+    }
+
+    #[test]
+    fn runoff_round_closes_only_after_its_last_ballot() {
+        let mut voting_question = question(3);
+        voting_question.ballot_type = BallotType::RankedChoice;
+        voting_question.total_ballots = 4;
+        voting_question.total_weight = 4;
+        let mut runoff = run_runoff(&[], 3);
+        runoff.is_complete = false;
+        // A first batch stops short of the round's last ballot
+        assert!(!count_runoff_ballot(&mut runoff, &voting_question, &[0, 1, 2], 1).unwrap());
+        assert!(!count_runoff_ballot(&mut runoff, &voting_question, &[1, 0, 2], 1).unwrap());
+        assert!(!count_runoff_ballot(&mut runoff, &voting_question, &[0, 1, 2], 1).unwrap());
+        assert_eq!(runoff.round, 0);
+        assert_eq!(runoff.round_counts, vec![2, 1, 0]);
+        // The next batch closes it and starts the following round afresh
+        assert!(count_runoff_ballot(&mut runoff, &voting_question, &[2, 0, 1], 1).unwrap());
+        assert!(!runoff.is_complete);
+        assert_eq!(runoff.round, 1);
+        assert_eq!(runoff.eliminated, vec![1, 2]);
+        assert_eq!(runoff.round_counts, vec![0, 0, 0]);
+        assert_eq!(runoff.ballots_processed, 0);
+    }
+
+    fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> TokenAccount {
+        use anchor_lang::solana_program::program_pack::Pack;
+        use anchor_spl::token::spl_token::state::{Account, AccountState};
+        let mut data = vec![0; TokenAccount::LEN];
+        Account { mint, owner, amount, state: AccountState::Initialized, ..Default::default() }
+            .pack_into_slice(&mut data);
+        TokenAccount::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    #[test]
+    fn token_weight_is_the_voters_balance_of_the_mint() {
+        let (mint, voter) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let holding = token_account(mint, voter, 250);
+        assert_eq!(token_balance_weight(Some(&holding), mint, voter).unwrap(), 250);
+        assert_eq!(
+            token_balance_weight(None, mint, voter).unwrap_err(),
+            WeightError::MissingTokenAccount.into()
+        );
+        let other_mint = token_account(Pubkey::new_unique(), voter, 250);
+        assert_eq!(
+            token_balance_weight(Some(&other_mint), mint, voter).unwrap_err(),
+            WeightError::WrongMint.into()
+        );
+        let borrowed = token_account(mint, Pubkey::new_unique(), 250);
+        assert_eq!(
+            token_balance_weight(Some(&borrowed), mint, voter).unwrap_err(),
+            WeightError::WrongTokenOwner.into()
+        );
+    }
+
+    #[test]
+    fn weighted_ballots_count_weight_per_option_and_once_per_voter() {
+        let mut voting_question = question(2);
+        cast(&mut voting_question, &[0], 250).unwrap();
+        cast(&mut voting_question, &[1], 40).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![250, 40]);
+        assert_eq!(voting_question.total_ballots, 2);
+        assert_eq!(voting_question.total_weight, 290);
+    }
+
+    #[test]
+    fn quorum_counts_voters_while_the_threshold_counts_weight() {
+        // Two small holders outnumber one whale, but the whale holds the majority
+        let mut voting_question = question(2);
+        cast(&mut voting_question, &[0], 100).unwrap();
+        cast(&mut voting_question, &[1], 1).unwrap();
+        cast(&mut voting_question, &[1], 1).unwrap();
+        voting_question.quorum = QuorumRule::Absolute { min_ballots: 3 };
+        assert_eq!(
+            question_outcome(&voting_question, &voting_question.vote_counts, &[0]),
+            QuestionOutcome::Passed
+        );
+        voting_question.quorum = QuorumRule::Absolute { min_ballots: 4 };
+        assert_eq!(
+            question_outcome(&voting_question, &voting_question.vote_counts, &[0]),
+            QuestionOutcome::QuorumNotMet
+        );
+    }
+
+    fn escrow(community: Pubkey, owner: Pubkey, mint: Pubkey, amount: u64) -> VoterEscrow {
+        VoterEscrow { community, owner, mint, amount, locked_until: 0 }
+    }
+
+    #[test]
+    fn escrow_weight_is_the_locked_amount_of_the_voting_mint() {
+        let (community, voter, mint) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let locked = escrow(community, voter, mint, 75);
+        assert_eq!(escrow_weight(Some(&locked), community, mint, voter).unwrap(), 75);
+        assert_eq!(
+            escrow_weight(None, community, mint, voter).unwrap_err(),
+            WeightError::MissingEscrow.into()
+        );
+        assert_eq!(
+            escrow_weight(Some(&locked), Pubkey::new_unique(), mint, voter).unwrap_err(),
+            EscrowError::WrongEscrow.into()
+        );
+        assert_eq!(
+            escrow_weight(Some(&locked), community, mint, Pubkey::new_unique()).unwrap_err(),
+            EscrowError::WrongEscrow.into()
+        );
+        assert_eq!(
+            escrow_weight(Some(&locked), community, Pubkey::new_unique(), voter).unwrap_err(),
+            WeightError::WrongMint.into()
+        );
+    }
+
+    #[test]
+    fn deposits_accumulate_in_a_single_mint() {
+        let mint = Pubkey::new_unique();
+        let mut locked = escrow(Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::default(), 0);
+        lock_tokens(&mut locked, mint, 40).unwrap();
+        lock_tokens(&mut locked, mint, 60).unwrap();
+        assert_eq!(locked.mint, mint);
+        assert_eq!(locked.amount, 100);
+        assert_eq!(lock_tokens(&mut locked, mint, 0).unwrap_err(), EscrowError::InvalidAmount.into());
+        assert_eq!(
+            lock_tokens(&mut locked, Pubkey::new_unique(), 10).unwrap_err(),
+            EscrowError::WrongMint.into()
+        );
+        // An emptied escrow can switch to a new voting mint
+        locked.amount = 0;
+        let new_mint = Pubkey::new_unique();
+        lock_tokens(&mut locked, new_mint, 10).unwrap();
+        assert_eq!(locked.mint, new_mint);
+    }
+
+    #[test]
+    fn withdrawals_wait_for_the_last_voted_deadline() {
+        let mut locked = escrow(Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), 100);
+        locked.locked_until = 1_000;
+        assert_eq!(unlock_tokens(&mut locked, 10, 1_000).unwrap_err(), EscrowError::TokensLocked.into());
+        assert_eq!(
+            unlock_tokens(&mut locked, 101, 1_001).unwrap_err(),
+            EscrowError::InsufficientEscrow.into()
+        );
+        assert_eq!(unlock_tokens(&mut locked, 0, 1_001).unwrap_err(), EscrowError::InvalidAmount.into());
+        unlock_tokens(&mut locked, 30, 1_001).unwrap();
+        assert_eq!(locked.amount, 70);
+        unlock_tokens(&mut locked, 70, 1_001).unwrap();
+        assert_eq!(locked.amount, 0);
+    }
+
+    #[test]
+    fn quadratic_allocation_costs_squares() {
+        assert_eq!(quadratic_cost(&[]), 0);
+        assert_eq!(quadratic_cost(&[3, 0, 4]), 25);
+        assert_eq!(quadratic_cost(&[u16::MAX; 4]), 4 * 65_535 * 65_535);
+
+        let mut voting_question = question(3);
+        voting_question.ballot_type = BallotType::Quadratic { voice_credits: 25 };
+        assert!(validate_selection(&voting_question, &[0, 2], &[3, 0, 4]).is_ok());
+        assert_eq!(
+            validate_selection(&voting_question, &[0, 1, 2], &[3, 1, 4]).unwrap_err(),
+            VotingQuestionError::InsufficientVoiceCredits.into()
+        );
+        assert_eq!(
+            validate_selection(&voting_question, &[0], &[3, 0]).unwrap_err(),
+            VotingQuestionError::InvalidAllocation.into()
+        );
+        assert_eq!(
+            validate_selection(&voting_question, &[], &[0, 0, 0]).unwrap_err(),
+            VotingQuestionError::InvalidAllocation.into()
+        );
+    }
+
+    #[test]
+    fn quadratic_ballots_add_votes_times_weight() {
+        let mut voting_question = question(3);
+        voting_question.ballot_type = BallotType::Quadratic { voice_credits: 25 };
+        // Each selected option receives its votes times the ballot's weight
+        let contributions = ballot_contributions(voting_question.ballot_type, &[0, 2], &[3, 0, 4], 2).unwrap();
+        assert_eq!(contributions, vec![(0, 6), (2, 8)]);
+        adjust_tally(&mut voting_question, contributions, 1, 2, true).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![6, 0, 8]);
+        assert_eq!(voting_question.total_ballots, 1);
+        assert_eq!(voting_question.total_weight, 2);
+    }
+
+    #[test]
+    fn outcome_compares_quadratic_votes_against_all_effective_votes() {
+        let mut voting_question = question(3);
+        voting_question.ballot_type = BallotType::Quadratic { voice_credits: 25 };
+        voting_question.total_ballots = 2;
+        voting_question.total_weight = 2;
+        assert_eq!(question_outcome(&voting_question, &[6, 0, 5], &[0]), QuestionOutcome::Passed);
+        // 5 of 11 effective votes is no majority, however it compares with the ballots' weight
+        assert_eq!(question_outcome(&voting_question, &[5, 2, 4], &[0]), QuestionOutcome::Failed);
+    }
+
+    fn member(community: Pubkey) -> Member {
+        Member {
+            version: MEMBER_VERSION,
+            community,
+            wallet: Pubkey::new_unique(),
+            role: MemberRole::Member,
+            joined_at: 0,
+            status: MemberStatus::Approved,
+            delegations_received: 0,
+            delegations_given: 0,
+            permissions: role_permissions(MemberRole::Member),
+        }
+    }
+
+    fn ballot(voting_question: &VotingQuestion, selected_options: &[u8], weight: u64) -> Vote {
+        Vote {
+            version: VOTE_VERSION,
+            question: Pubkey::new_unique(),
+            voter: Pubkey::new_unique(),
+            selected_option: selected_options[0],
+            voted_at: 0,
+            selected_options: selected_options.to_vec(),
+            tallied_round: 0,
+            weight,
+            allocation: vec![0; voting_question.options.len()],
+            delegated_to: None,
+            delegated_weight: 0,
+            commitment: None,
+            revealed: false,
+        }
+    }
+
+    #[test]
+    fn delegation_is_a_single_hop_in_every_scope() {
+        let community = Pubkey::new_unique();
+        let (mut alice, mut bob, mut carol) = (member(community), member(community), member(community));
+        assert_eq!(
+            link_delegation(&mut alice.clone(), &mut alice).unwrap_err(),
+            DelegationError::SelfDelegation.into()
+        );
+        link_delegation(&mut alice, &mut bob).unwrap();
+        assert_eq!((alice.delegations_given, bob.delegations_received), (1, 1));
+        // Bob holds Alice's vote, so can't pass it on, whatever the scope of either delegation
+        assert_eq!(
+            link_delegation(&mut bob, &mut carol).unwrap_err(),
+            DelegationError::DelegatorHoldsDelegations.into()
+        );
+        // Alice has delegated, so nobody can delegate to her
+        assert_eq!(
+            link_delegation(&mut carol, &mut alice).unwrap_err(),
+            DelegationError::DelegateHasDelegated.into()
+        );
+        // A second, per-question delegation from Alice is fine
+        link_delegation(&mut alice, &mut carol).unwrap();
+        assert_eq!(alice.delegations_given, 2);
+
+        unlink_delegation(&mut alice, &mut bob);
+        unlink_delegation(&mut alice, &mut carol);
+        assert_eq!((alice.delegations_given, bob.delegations_received), (0, 0));
+        // Once revoked, Bob is free to delegate and Alice to receive
+        link_delegation(&mut bob, &mut alice).unwrap();
+    }
+
+    #[test]
+    fn claims_need_a_delegation_to_the_delegate_for_the_question() {
+        let (community, question_key, delegate) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut delegation = Delegation {
+            community,
+            delegator: Pubkey::new_unique(),
+            delegate,
+            question: None,
+            created_at: 0,
+        };
+        assert!(check_delegation_applies(&delegation, community, question_key, delegate, false).is_ok());
+        assert_eq!(
+            check_delegation_applies(&delegation, community, question_key, Pubkey::new_unique(), false).unwrap_err(),
+            DelegationError::WrongDelegate.into()
+        );
+        assert_eq!(
+            check_delegation_applies(&delegation, Pubkey::new_unique(), question_key, delegate, false).unwrap_err(),
+            DelegationError::WrongDelegate.into()
+        );
+        // A per-question delegation elsewhere overrides the global one for that question
+        assert_eq!(
+            check_delegation_applies(&delegation, community, question_key, delegate, true).unwrap_err(),
+            DelegationError::OutOfScope.into()
+        );
+        delegation.question = Some(question_key);
+        assert!(check_delegation_applies(&delegation, community, question_key, delegate, true).is_ok());
+        assert_eq!(
+            check_delegation_applies(&delegation, community, Pubkey::new_unique(), delegate, false).unwrap_err(),
+            DelegationError::OutOfScope.into()
+        );
+    }
+
+    #[test]
+    fn claimed_weight_follows_the_delegates_ballot_until_overridden() {
+        let mut voting_question = question(3);
+        voting_question.ballot_type = BallotType::MultipleChoice { min_selections: 1, max_selections: 2 };
+        let mut delegate_vote = ballot(&voting_question, &[0, 2], 1);
+        let contributions = ballot_contributions(voting_question.ballot_type, &[0, 2], &[], 1).unwrap();
+        adjust_tally(&mut voting_question, contributions, 1, 1, true).unwrap();
+
+        // Two delegators' weight counts for every option the delegate chose
+        add_delegated_weight(&mut voting_question, &mut delegate_vote, 1).unwrap();
+        add_delegated_weight(&mut voting_question, &mut delegate_vote, 1).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![3, 0, 3]);
+        assert_eq!((voting_question.total_ballots, voting_question.total_weight), (3, 3));
+        assert_eq!((delegate_vote.weight, delegate_vote.delegated_weight), (3, 2));
+
+        // One of them votes directly: their weight leaves the delegate's ballot
+        remove_delegated_weight(&mut voting_question, &mut delegate_vote, 1).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![2, 0, 2]);
+        assert_eq!((voting_question.total_ballots, voting_question.total_weight), (2, 2));
+        assert_eq!((delegate_vote.weight, delegate_vote.delegated_weight), (2, 1));
+
+        // More than was delegated can never be taken back
+        assert_eq!(
+            remove_delegated_weight(&mut voting_question, &mut delegate_vote, 2).unwrap_err(),
+            VotingQuestionError::TallyOverflow.into()
+        );
+    }
+
+    #[test]
+    fn commitment_hashes_question_voter_option_and_salt() {
+        let question = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let salt = [7u8; 32];
+        let commitment = vote_commitment(&question, &voter, 2, &salt);
+        // Clients commit to sha256(question || voter || option || salt)
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(question.as_ref());
+        preimage.extend_from_slice(voter.as_ref());
+        preimage.push(2);
+        preimage.extend_from_slice(&salt);
+        assert_eq!(commitment, anchor_lang::solana_program::hash::hash(&preimage).to_bytes());
+
+        // Every input is bound: changing any one of them changes the commitment
+        assert_ne!(commitment, vote_commitment(&Pubkey::new_unique(), &voter, 2, &salt));
+        assert_ne!(commitment, vote_commitment(&question, &Pubkey::new_unique(), 2, &salt));
+        assert_ne!(commitment, vote_commitment(&question, &voter, 1, &salt));
+        assert_ne!(commitment, vote_commitment(&question, &voter, 2, &[8u8; 32]));
+    }
+
+    #[test]
+    fn secret_ballots_count_only_once_revealed_in_the_reveal_period() {
+        let question_key = Pubkey::new_unique();
+        let mut voting_question = question(3);
+        voting_question.deadline = 100;
+        voting_question.reveal_deadline = Some(200);
+        let mut vote = ballot(&voting_question, &[0], 5);
+        vote.selected_options = Vec::new();
+        let salt = [3u8; 32];
+        vote.commitment = Some(vote_commitment(&question_key, &vote.voter, 1, &salt));
+
+        // Reveals open once voting closes and end at the reveal deadline
+        for now in [100, 201] {
+            assert_eq!(
+                reveal_ballot(&mut voting_question, question_key, &mut vote, 1, &salt, now).unwrap_err(),
+                CommitRevealError::NotRevealPeriod.into()
+            );
+        }
+        assert_eq!(
+            reveal_ballot(&mut voting_question, question_key, &mut vote, 2, &salt, 150).unwrap_err(),
+            CommitRevealError::CommitmentMismatch.into()
+        );
+        assert_eq!(voting_question.total_ballots, 0);
+
+        reveal_ballot(&mut voting_question, question_key, &mut vote, 1, &salt, 200).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![0, 5, 0]);
+        assert_eq!((voting_question.total_ballots, voting_question.total_weight), (1, 5));
+        assert_eq!(vote.selected_options, vec![1]);
+        // A commitment opens once
+        assert_eq!(
+            reveal_ballot(&mut voting_question, question_key, &mut vote, 1, &salt, 200).unwrap_err(),
+            CommitRevealError::NothingToReveal.into()
+        );
+
+        voting_question.reveal_deadline = None;
+        assert_eq!(
+            reveal_ballot(&mut voting_question, question_key, &mut vote, 1, &salt, 150).unwrap_err(),
+            CommitRevealError::NotSecretBallot.into()
+        );
+    }
+
+
+    #[test]
+    fn vote_edits_need_an_open_direct_ballot() {
+        let mut community = Community {
+            version: COMMUNITY_VERSION,
+            admins: vec![Pubkey::new_unique()],
+            name: "C".to_string(),
+            description: "D".to_string(),
+            member_count: 1,
+            created_at: 0,
+            config: config(QuorumRule::None, PassThreshold::SimpleMajority),
+            question_count: 1,
+            slug: "c".to_string(),
+            status: CommunityStatus::Active,
+            pending_admin: None,
+            admin_threshold: 1,
+            admin_proposal_count: 0,
+        };
+        let mut voting_question = question(2);
+        voting_question.deadline = 100;
+        let mut vote = ballot(&voting_question, &[0], 1);
+        assert!(ensure_vote_editable(&voting_question, &community, &vote, 100).is_ok());
+        assert_eq!(
+            ensure_vote_editable(&voting_question, &community, &vote, 101).unwrap_err(),
+            VotingQuestionError::DeadlinePassed.into()
+        );
+
+        vote.delegated_to = Some(Pubkey::new_unique());
+        assert_eq!(
+            ensure_vote_editable(&voting_question, &community, &vote, 0).unwrap_err(),
+            VoteChangeError::NotDirectVote.into()
+        );
+        vote.delegated_to = None;
+
+        voting_question.reveal_deadline = Some(200);
+        assert_eq!(
+            ensure_vote_editable(&voting_question, &community, &vote, 0).unwrap_err(),
+            VoteChangeError::SecretBallot.into()
+        );
+        voting_question.reveal_deadline = None;
+
+        voting_question.is_active = false;
+        assert_eq!(
+            ensure_vote_editable(&voting_question, &community, &vote, 0).unwrap_err(),
+            VotingQuestionError::NotActive.into()
+        );
+        voting_question.is_active = true;
+
+        community.status = CommunityStatus::Dissolved;
+        assert_eq!(
+            ensure_vote_editable(&voting_question, &community, &vote, 0).unwrap_err(),
+            EdgeCaseError::CommunityDissolved.into()
+        );
+    }
+
+    #[test]
+    fn changing_and_retracting_a_vote_moves_the_tally() {
+        let mut voting_question = question(3);
+        voting_question.ballot_type = BallotType::MultipleChoice { min_selections: 1, max_selections: 2 };
+        let cast = ballot_contributions(voting_question.ballot_type, &[0, 1], &[], 3).unwrap();
+        adjust_tally(&mut voting_question, cast.clone(), 1, 3, true).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![3, 3, 0]);
+
+        // A change swaps contributions but keeps the ballot and its weight
+        let changed = ballot_contributions(voting_question.ballot_type, &[2], &[], 3).unwrap();
+        adjust_tally(&mut voting_question, cast, 0, 0, false).unwrap();
+        adjust_tally(&mut voting_question, changed.clone(), 0, 0, true).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![0, 0, 3]);
+        assert_eq!(voting_question.total_ballots, 1);
+        assert_eq!(voting_question.total_weight, 3);
+
+        // A retraction removes the ballot entirely
+        adjust_tally(&mut voting_question, changed, 1, 3, false).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![0, 0, 0]);
+        assert_eq!(voting_question.total_ballots, 0);
+        assert_eq!(voting_question.total_weight, 0);
+
+        // Removing more than was counted is an error, not a wrap-around
+        assert_eq!(
+            adjust_tally(&mut voting_question, vec![(0, 1)], 0, 0, false).unwrap_err(),
+            VotingQuestionError::TallyOverflow.into()
+        );
+    }
+
+
+    #[test]
+    fn questions_take_consecutive_indexes() {
+        let mut community = Community {
+            version: COMMUNITY_VERSION,
+            admins: vec![Pubkey::new_unique()],
+            name: "C".to_string(),
+            description: "D".to_string(),
+            member_count: 1,
+            created_at: 0,
+            config: config(QuorumRule::None, PassThreshold::SimpleMajority),
+            question_count: 0,
+            slug: "c".to_string(),
+            status: CommunityStatus::Active,
+            pending_admin: None,
+            admin_threshold: 1,
+            admin_proposal_count: 0,
+        };
+        assert_eq!(next_question_index(&mut community).unwrap(), 0);
+        assert_eq!(next_question_index(&mut community).unwrap(), 1);
+        assert_eq!(community.question_count, 2);
+
+        // The sequence never wraps back onto an existing question's address
+        community.question_count = u64::MAX;
+        assert_eq!(next_question_index(&mut community).unwrap_err(), VotingError::CounterOverflow.into());
+        assert_eq!(community.question_count, u64::MAX);
+    }
+
+
+    #[test]
+    fn slugs_normalize_names() {
+        assert_eq!(community_slug("Test Community"), "test-community");
+        assert_eq!(community_slug("My  Community!"), "my-community");
+        assert_eq!(community_slug("--Rust__Devs 2024--"), "rust-devs-2024");
+        assert_eq!(community_slug("!!!"), "");
+        // Letters outside ASCII are kept, so distinct non-English names stay distinct
+        assert_eq!(community_slug("Café Crème"), "café-crème");
+        assert_eq!(community_slug("ΔΗΜΟΣ"), "δημοσ");
+        assert_ne!(community_address("Café"), community_address("Cafe"));
+        assert_ne!(community_address("東京"), community_address("大阪"));
+        // Names that normalize alike share an address
+        assert_eq!(community_address("My Community"), community_address("my-community"));
+        assert_ne!(community_address("My Community"), community_address("My Community 2"));
+    }
+
+    #[test]
+    fn slugs_fit_their_limit_without_a_trailing_dash() {
+        assert_eq!(community_slug(&"a".repeat(40)).len(), MAX_SLUG_LEN);
+        // Truncation landing right after a separator drops it
+        let name = format!("{} b", "a".repeat(MAX_SLUG_LEN - 1));
+        assert_eq!(community_slug(&name), "a".repeat(MAX_SLUG_LEN - 1));
+        // Multi-byte characters are never split
+        let slug = community_slug(&"é".repeat(20));
+        assert_eq!(slug, "é".repeat(MAX_SLUG_LEN / 2));
+        let slug = community_slug(&format!("a{}", "é".repeat(20)));
+        assert_eq!(slug, format!("a{}", "é".repeat((MAX_SLUG_LEN - 1) / 2)));
+    }
+
+
+    #[test]
+    fn lifecycle_enums_keep_the_old_byte_codes() {
+        assert_eq!(MemberStatus::Pending.try_to_vec().unwrap(), vec![0]);
+        assert_eq!(MemberStatus::Approved.try_to_vec().unwrap(), vec![1]);
+        assert_eq!(MemberStatus::Rejected.try_to_vec().unwrap(), vec![2]);
+        assert_eq!(MemberRole::Member.try_to_vec().unwrap(), vec![0]);
+        assert_eq!(MemberRole::Admin.try_to_vec().unwrap(), vec![1]);
+        // A zeroed byte appended by migration reads as an active community
+        assert_eq!(CommunityStatus::try_from_slice(&[0]).unwrap(), CommunityStatus::Active);
+    }
+
+    #[test]
+    fn only_active_communities_take_part() {
+        let mut community = Community {
+            version: COMMUNITY_VERSION,
+            admins: vec![Pubkey::new_unique()],
+            name: "C".to_string(),
+            description: "D".to_string(),
+            member_count: 3,
+            created_at: 0,
+            config: config(QuorumRule::None, PassThreshold::SimpleMajority),
+            question_count: 0,
+            slug: "c".to_string(),
+            status: CommunityStatus::Active,
+            pending_admin: None,
+            admin_threshold: 1,
+            admin_proposal_count: 0,
+        };
+        assert!(ensure_community_active(&community).is_ok());
+        community.status = CommunityStatus::Paused;
+        assert_eq!(ensure_community_active(&community).unwrap_err(), EdgeCaseError::CommunityPaused.into());
+        community.status = CommunityStatus::Dissolved;
+        assert_eq!(ensure_community_active(&community).unwrap_err(), EdgeCaseError::CommunityDissolved.into());
+
+        // Migration turns the old sentinel into the Dissolved status
+        community.status = CommunityStatus::Active;
+        replace_dissolved_sentinel(&mut community);
+        assert_eq!((community.status, community.member_count), (CommunityStatus::Active, 3));
+        community.member_count = u32::MAX;
+        replace_dissolved_sentinel(&mut community);
+        assert_eq!((community.status, community.member_count), (CommunityStatus::Dissolved, 0));
+    }
+
+
+    // An account's data as the program would store it, padded to its allocated size
+    fn account_data<T: AccountSerialize>(account: &T, space: usize) -> Vec<u8> {
+        let mut data = vec![0; space];
+        account.try_serialize(&mut &mut data[..]).unwrap();
+        data
+    }
+
+    // An unversioned account as originally deployed
+    fn v0_data<T: AnchorSerialize>(discriminator: [u8; 8], body: &T, space: usize) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        body.serialize(&mut data).unwrap();
+        data.resize(space, 0);
+        data
+    }
+
+    #[test]
+    fn versions_are_read_from_the_size_or_the_version_byte() {
+        let discriminator = <Vote as Discriminator>::DISCRIMINATOR;
+        let old = VoteV0 { question: Pubkey::new_unique(), voter: Pubkey::new_unique(), selected_option: 1, voted_at: 5 };
+        assert_eq!(stored_version(&v0_data(discriminator, &old, VOTE_V0_SPACE), discriminator, VOTE_V0_SPACE).unwrap(), 0);
+
+        let voting_question = question(2);
+        let vote = ballot(&voting_question, &[1], 1);
+        let data = account_data(&vote, VOTE_SPACE);
+        assert_eq!(stored_version(&data, discriminator, VOTE_V0_SPACE).unwrap(), VOTE_VERSION);
+        assert_eq!(
+            stored_version(&data, <Member as Discriminator>::DISCRIMINATOR, MEMBER_V0_SPACE).unwrap_err(),
+            MigrationError::WrongAccountType.into()
+        );
+
+        // A migrated account never lands back on its unversioned size
+        assert_ne!(COMMUNITY_SPACE, COMMUNITY_V0_SPACE);
+        assert_ne!(MEMBER_SPACE, MEMBER_V0_SPACE);
+        assert_ne!(QUESTION_SPACE, QUESTION_V0_SPACE);
+        assert_ne!(VOTE_SPACE, VOTE_V0_SPACE);
+    }
+
+    #[test]
+    fn communities_migrate_once_from_the_deployed_layout() {
+        let discriminator = <Community as Discriminator>::DISCRIMINATOR;
+        let old = CommunityV0 {
+            admin: Pubkey::new_unique(),
+            name: "Old".to_string(),
+            description: "D".to_string(),
+            member_count: 4,
+            created_at: 7,
+            config: CommunityConfigV0 { voting_period: 3600, max_options: 3 },
+        };
+        let data = v0_data(discriminator, &old, COMMUNITY_V0_SPACE);
+        let version = stored_version(&data, discriminator, COMMUNITY_V0_SPACE).unwrap();
+        let community = migrated_community(version, &data[8..]).unwrap();
+        assert_eq!(community.version, COMMUNITY_VERSION);
+        assert_eq!(community.admins, vec![old.admin]);
+        assert_eq!((community.member_count, community.created_at), (4, 7));
+        assert_eq!((community.config.voting_period, community.config.max_options), (3600, 3));
+        assert!(community.config.quorum == QuorumRule::None);
+        assert_eq!(community.status, CommunityStatus::Active);
+        assert_eq!(community.slug, "");
+
+        // Migrating the rewritten account again is refused rather than misreading it
+        let data = account_data(&community, COMMUNITY_SPACE);
+        let version = stored_version(&data, discriminator, COMMUNITY_V0_SPACE).unwrap();
+        assert_eq!(migrated_community(version, &data[8..]).err().unwrap(), MigrationError::AlreadyCurrent.into());
+        assert_eq!(migrated_community(9, &data[8..]).err().unwrap(), MigrationError::UnknownVersion.into());
+
+        // The old dissolved sentinel becomes the status
+        let dissolved = CommunityV0 { member_count: u32::MAX, ..old };
+        let community = migrated_community(0, &v0_data(discriminator, &dissolved, COMMUNITY_V0_SPACE)[8..]).unwrap();
+        assert_eq!((community.status, community.member_count), (CommunityStatus::Dissolved, 0));
+    }
+
+    #[test]
+    fn members_migrate_once_with_their_role_and_status() {
+        let discriminator = <Member as Discriminator>::DISCRIMINATOR;
+        let old = MemberV0 {
+            community: Pubkey::new_unique(),
+            wallet: Pubkey::new_unique(),
+            role: MemberRole::Admin,
+            joined_at: 3,
+            status: MemberStatus::Approved,
+        };
+        let data = v0_data(discriminator, &old, MEMBER_V0_SPACE);
+        let member = migrated_member(stored_version(&data, discriminator, MEMBER_V0_SPACE).unwrap(), &data[8..]).unwrap();
+        assert_eq!((member.wallet, member.role, member.status), (old.wallet, MemberRole::Admin, MemberStatus::Approved));
+        assert_eq!((member.delegations_received, member.delegations_given), (0, 0));
+        assert_eq!(member.permissions, ALL_PERMISSIONS);
+
+        let data = account_data(&member, MEMBER_SPACE);
+        let version = stored_version(&data, discriminator, MEMBER_V0_SPACE).unwrap();
+        assert_eq!(migrated_member(version, &data[8..]).err().unwrap(), MigrationError::AlreadyCurrent.into());
+    }
+
+    #[test]
+    fn migrated_votes_rebuild_their_question_tally() {
+        let old = VotingQuestionV0 {
+            community: Pubkey::new_unique(),
+            creator: Pubkey::new_unique(),
+            question: "Q".to_string(),
+            options: vec!["A".to_string(), "B".to_string()],
+            deadline: 100,
+            created_at: 0,
+            is_active: true,
+        };
+        let data = v0_data(<VotingQuestion as Discriminator>::DISCRIMINATOR, &old, QUESTION_V0_SPACE);
+        let mut voting_question = migrated_voting_question(0, &data[8..], 5).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![0, 0]);
+        assert_eq!(voting_question.eligible_voters, 5);
+        let data = account_data(&voting_question, QUESTION_SPACE);
+        assert_eq!(
+            migrated_voting_question(data[8], &data[8..], 5).err().unwrap(),
+            MigrationError::AlreadyCurrent.into()
+        );
+
+        let discriminator = <Vote as Discriminator>::DISCRIMINATOR;
+        for selected_option in [1, 1, 0] {
+            let old = VoteV0 { question: Pubkey::new_unique(), voter: Pubkey::new_unique(), selected_option, voted_at: 1 };
+            let vote = migrated_vote(0, &v0_data(discriminator, &old, VOTE_V0_SPACE)[8..]).unwrap();
+            assert_eq!((vote.selected_options.clone(), vote.weight), (vec![selected_option], 1));
+            recount_migrated_vote(&mut voting_question, &vote).unwrap();
+        }
+        assert_eq!(voting_question.vote_counts, vec![1, 2]);
+        assert_eq!((voting_question.total_ballots, voting_question.total_weight), (3, 3));
+
+        // A finalized result is left alone
+        voting_question.is_finalized = true;
+        let vote = ballot(&voting_question, &[0], 1);
+        recount_migrated_vote(&mut voting_question, &vote).unwrap();
+        assert_eq!(voting_question.vote_counts, vec![1, 2]);
+    }
+
+
+    fn serialized_len<T: AccountSerialize>(account: &T) -> usize {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data.len()
+    }
+
+    fn text(len: usize) -> String {
+        "x".repeat(len)
+    }
+
+    // Largest variant of every config enum
+    fn largest_config() -> CommunityConfig {
+        CommunityConfig {
+            voting_period: i64::MAX,
+            min_voting_period: i64::MAX,
+            max_options: MAX_OPTIONS as u8,
+            quorum: QuorumRule::Absolute { min_ballots: u32::MAX },
+            pass_threshold: PassThreshold::Supermajority { bps: 10_000 },
+            vote_weighting: VoteWeighting::LockedTokens { mint: Pubkey::new_unique() },
+        }
+    }
+
+    fn largest_rounds() -> Vec<RunoffRound> {
+        vec![
+            RunoffRound { vote_counts: vec![u64::MAX; MAX_OPTIONS], eliminated: vec![0; MAX_OPTIONS] };
+            MAX_OPTIONS
+        ]
+    }
+
+    #[test]
+    fn worst_case_community_fits() {
+        let community = Community {
+            version: COMMUNITY_VERSION,
+            admins: vec![Pubkey::new_unique(); MAX_ADMINS],
+            name: text(MAX_NAME_LEN),
+            description: text(MAX_DESCRIPTION_LEN),
+            member_count: u32::MAX,
+            created_at: i64::MAX,
+            config: largest_config(),
+            question_count: u64::MAX,
+            slug: text(MAX_SLUG_LEN),
+            status: CommunityStatus::Dissolved,
+            pending_admin: Some(AdminTransfer {
+                from: Pubkey::new_unique(),
+                to: Pubkey::new_unique(),
+                proposed_at: i64::MAX,
+                expires_at: Some(i64::MAX),
+            }),
+            admin_threshold: u8::MAX,
+            admin_proposal_count: u64::MAX,
+        };
+        assert_eq!(serialized_len(&community), COMMUNITY_SPACE);
+    }
+
+    #[test]
+    fn worst_case_member_fits() {
+        let member = Member {
+            version: MEMBER_VERSION,
+            community: Pubkey::new_unique(),
+            wallet: Pubkey::new_unique(),
+            role: MemberRole::Admin,
+            joined_at: i64::MAX,
+            status: MemberStatus::Removed,
+            delegations_received: u32::MAX,
+            delegations_given: u32::MAX,
+            permissions: ALL_PERMISSIONS,
+        };
+        assert_eq!(serialized_len(&member), MEMBER_SPACE);
+    }
+
+    #[test]
+    fn worst_case_voting_question_fits() {
+        let voting_question = VotingQuestion {
+            version: QUESTION_VERSION,
+            community: Pubkey::new_unique(),
+            creator: Pubkey::new_unique(),
+            question: text(MAX_QUESTION_LEN),
+            options: vec![text(MAX_OPTION_LEN); MAX_OPTIONS],
+            deadline: i64::MAX,
+            created_at: i64::MAX,
+            is_active: true,
+            vote_counts: vec![u64::MAX; MAX_OPTIONS],
+            total_ballots: u64::MAX,
+            eligible_voters: u32::MAX,
+            is_finalized: true,
+            quorum: QuorumRule::Absolute { min_ballots: u32::MAX },
+            pass_threshold: PassThreshold::Supermajority { bps: 10_000 },
+            ballot_type: BallotType::Quadratic { voice_credits: u32::MAX },
+            vote_weighting: VoteWeighting::LockedTokens { mint: Pubkey::new_unique() },
+            total_weight: u64::MAX,
+            reveal_deadline: Some(i64::MAX),
+            total_commits: u64::MAX,
+            index: u64::MAX,
+            starts_at: Some(i64::MAX),
+            action: Some(CommunityAction::ReplaceAdmin {
+                old_admin: Pubkey::new_unique(),
+                new_admin: Pubkey::new_unique(),
+            }),
+            action_executed: true,
+        };
+        assert_eq!(serialized_len(&voting_question), QUESTION_SPACE);
+    }
+
+    #[test]
+    fn worst_case_vote_fits() {
+        let vote = Vote {
+            version: VOTE_VERSION,
+            question: Pubkey::new_unique(),
+            voter: Pubkey::new_unique(),
+            selected_option: 0,
+            voted_at: i64::MAX,
+            selected_options: vec![0; MAX_OPTIONS],
+            tallied_round: u8::MAX,
+            weight: u64::MAX,
+            allocation: vec![u16::MAX; MAX_OPTIONS],
+            delegated_to: Some(Pubkey::new_unique()),
+            delegated_weight: u64::MAX,
+            commitment: Some([0xff; 32]),
+            revealed: true,
+        };
+        assert_eq!(serialized_len(&vote), VOTE_SPACE);
+    }
+
+    #[test]
+    fn worst_case_result_and_runoff_fit() {
+        let result = QuestionResult {
+            question: Pubkey::new_unique(),
+            community: Pubkey::new_unique(),
+            winning_options: vec![0; MAX_OPTIONS],
+            vote_counts: vec![u64::MAX; MAX_OPTIONS],
+            total_ballots: u64::MAX,
+            total_weight: u64::MAX,
+            eligible_voters: u32::MAX,
+            turnout_bps: u16::MAX,
+            is_tie: true,
+            outcome: QuestionOutcome::QuorumNotMet,
+            finalized_at: i64::MAX,
+            runoff_rounds: largest_rounds(),
+            unrevealed_commits: u64::MAX,
+        };
+        assert_eq!(serialized_len(&result), 8 + QuestionResult::INIT_SPACE);
+
+        let runoff = RunoffTally {
+            question: Pubkey::new_unique(),
+            payer: Pubkey::new_unique(),
+            round: u8::MAX,
+            eliminated: vec![0; MAX_OPTIONS],
+            round_counts: vec![u64::MAX; MAX_OPTIONS],
+            ballots_processed: u64::MAX,
+            rounds: largest_rounds(),
+            winning_options: vec![0; MAX_OPTIONS],
+            is_complete: true,
+        };
+        assert_eq!(serialized_len(&runoff), 8 + RunoffTally::INIT_SPACE);
+    }
+
+    #[test]
+    fn worst_case_delegation_and_escrow_fit() {
+        let delegation = Delegation {
+            community: Pubkey::new_unique(),
+            delegator: Pubkey::new_unique(),
+            delegate: Pubkey::new_unique(),
+            question: Some(Pubkey::new_unique()),
+            created_at: i64::MAX,
+        };
+        assert_eq!(serialized_len(&delegation), 8 + Delegation::INIT_SPACE);
+
+        let escrow = VoterEscrow {
+            community: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            amount: u64::MAX,
+            locked_until: i64::MAX,
+        };
+        assert_eq!(serialized_len(&escrow), 8 + VoterEscrow::INIT_SPACE);
+    }
+
+    #[test]
+    fn worst_case_admin_proposal_fits() {
+        let proposal = AdminProposal {
+            community: Pubkey::new_unique(),
+            index: u64::MAX,
+            proposer: Pubkey::new_unique(),
+            action: CommunityAction::ReplaceAdmin {
+                old_admin: Pubkey::new_unique(),
+                new_admin: Pubkey::new_unique(),
+            },
+            approvals: vec![Pubkey::new_unique(); MAX_ADMINS],
+            created_at: i64::MAX,
+            expires_at: i64::MAX,
+        };
+        assert_eq!(serialized_len(&proposal), 8 + AdminProposal::INIT_SPACE);
+    }
 
-#[program]
-pub mod voting {
-    use super::*;
+    // Migrations tell unversioned accounts apart by size, so current sizes must not reuse them
+    #[test]
+    fn current_sizes_differ_from_unversioned_sizes() {
+        assert_ne!(COMMUNITY_SPACE, COMMUNITY_V0_SPACE);
+        assert_ne!(MEMBER_SPACE, MEMBER_V0_SPACE);
+        assert_ne!(QUESTION_SPACE, QUESTION_V0_SPACE);
+        assert_ne!(VOTE_SPACE, VOTE_V0_SPACE);
+    }
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        Ok(())
+    #[test]
+    fn question_text_limits() {
+        let options = vec![text(MAX_OPTION_LEN); MAX_OPTIONS];
+        assert!(validate_question_text(&text(MAX_QUESTION_LEN), &options).is_ok());
+        assert_eq!(
+            validate_question_text(&text(MAX_QUESTION_LEN + 1), &options).unwrap_err(),
+            VotingQuestionError::QuestionTooLong.into()
+        );
+        assert_eq!(
+            validate_question_text("Q", &vec![text(1); MAX_OPTIONS + 1]).unwrap_err(),
+            VotingQuestionError::TooManyOptions.into()
+        );
+        let mut long_option = options.clone();
+        long_option[MAX_OPTIONS - 1] = text(MAX_OPTION_LEN + 1);
+        assert_eq!(
+            validate_question_text("Q", &long_option).unwrap_err(),
+            VotingQuestionError::OptionTooLong.into()
+        );
     }
 
-    pub fn create_community(
-        ctx: Context<CreateCommunity>,
-        name: String,
-        description: String,
-        config: CommunityConfig,
-    ) -> Result<()> {
-        // Constraints
-        let max_name_len = 32;
-        let max_desc_len = 256;
-        if name.len() > max_name_len {
-            return err!(VotingError::NameTooLong);
-        }
-        if description.len() > max_desc_len {
-            return err!(VotingError::DescriptionTooLong);
-        }
-        if config.voting_period <= 0 {
-            return err!(VotingError::InvalidVotingPeriod);
-        }
-        if config.max_options < 2 || config.max_options > 4 {
-            return err!(VotingError::InvalidMaxOptions);
-        }
 
-        let community = &mut ctx.accounts.community;
-        community.admin = ctx.accounts.admin.key();
-        community.name = name;
-        community.description = description;
-        community.member_count = 1;
-        community.created_at = Clock::get()?.unix_timestamp;
-        community.config = config;
-        let now = Clock::get()?.unix_timestamp;
-        emit!(CommunityCreated {
-            community: ctx.accounts.community.key(),
-            admin: ctx.accounts.admin.key(),
-            name: ctx.accounts.community.name.clone(),
-            timestamp: now,
-        });
-        Ok(())
+    #[test]
+    fn members_close_only_once_nothing_refers_to_them() {
+        let community = Pubkey::new_unique();
+        let admin = Pubkey::new_unique();
+        let moderator = role_permissions(MemberRole::Moderator);
+        let mut removed = member(community);
+        removed.status = MemberStatus::Removed;
+        assert!(ensure_member_close_allowed(&removed, removed.wallet, 0).is_ok());
+        assert!(ensure_member_close_allowed(&removed, admin, ALL_PERMISSIONS).is_ok());
+        assert!(ensure_member_close_allowed(&removed, Pubkey::new_unique(), moderator).is_ok());
+        assert_eq!(
+            ensure_member_close_allowed(&removed, Pubkey::new_unique(), PERMISSION_CREATE_QUESTIONS).unwrap_err(),
+            CloseError::NotAuthorized.into()
+        );
+
+        let approved = member(community);
+        assert_eq!(
+            ensure_member_close_allowed(&approved, admin, ALL_PERMISSIONS).unwrap_err(),
+            CloseError::MemberStillActive.into()
+        );
+
+        // Delegations in either direction keep the account alive, whatever their scope
+        removed.delegations_received = 1;
+        assert_eq!(ensure_member_closable(&removed).unwrap_err(), CloseError::HoldsDelegations.into());
+        removed.delegations_received = 0;
+        removed.delegations_given = 2;
+        assert_eq!(ensure_member_closable(&removed).unwrap_err(), CloseError::HasDelegation.into());
     }
 
-    pub fn join_community(ctx: Context<JoinCommunity>) -> Result<()> {
-        let community = &mut ctx.accounts.community;
-        let member = &mut ctx.accounts.member;
-        // Prevent joining if community is dissolved
-        if community.member_count == u32::MAX {
-            return err!(EdgeCaseError::CommunityDissolved);
-        }
-        // Allow re-application only if previously rejected or removed
-        if member.status == 0 || member.status == 1 {
-            return err!(EdgeCaseError::AlreadyActive);
-        }
-        member.community = community.key();
-        member.wallet = ctx.accounts.user.key();
-        member.role = 0; // regular member
-        member.joined_at = Clock::get()?.unix_timestamp;
-        member.status = 0; // 0 = pending
-        let now = Clock::get()?.unix_timestamp;
-        emit!(MemberJoined {
-            community: ctx.accounts.community.key(),
-            member_wallet: ctx.accounts.user.key(),
-            timestamp: now,
-        });
-        Ok(())
+    #[test]
+    fn vote_rent_goes_back_to_its_payer() {
+        let voting_question = question(2);
+        let mut vote = ballot(&voting_question, &[0], 1);
+        assert_eq!(vote_rent_payer(&vote), vote.voter);
+        // A marker was created, and paid for, by the delegate
+        let delegate = Pubkey::new_unique();
+        vote.delegated_to = Some(delegate);
+        assert_eq!(vote_rent_payer(&vote), delegate);
     }
 
-    pub fn approve_member(
-        ctx: Context<ApproveMember>,
-        approve: bool, // true = approve, false = reject
-    ) -> Result<()> {
-        let community = &mut ctx.accounts.community;
-        let member = &mut ctx.accounts.member;
-        let admin = &ctx.accounts.admin;
 
-        // Only the community admin can approve/reject
-        if community.admin != *admin.key {
-            return err!(ApprovalError::NotAdmin);
-        }
-        // Only pending members can be approved/rejected
-        if member.status != 0 {
-            return err!(ApprovalError::NotPending);
-        }
-        if approve {
-            member.status = 1; // approved
-            community.member_count = community.member_count.checked_add(1).ok_or(ProgramError::InvalidArgument)?;
-            msg!("Member {} approved by admin {}", member.wallet, admin.key);
-        } else {
-            member.status = 2; // rejected
-            msg!("Member {} rejected by admin {}", member.wallet, admin.key);
-        }
-        let now = Clock::get()?.unix_timestamp;
-        emit!(MemberApproved {
-            community: ctx.accounts.community.key(),
-            member_wallet: member.wallet,
-            admin: *admin.key,
-            status: member.status,
-            timestamp: now,
-        });
-        Ok(())
+    #[test]
+    fn question_deadlines_follow_the_community_voting_period() {
+        let mut config = config(QuorumRule::None, PassThreshold::SimpleMajority);
+        config.min_voting_period = 600;
+        assert_eq!(question_deadline(&config, None, 1_000).unwrap(), 4_600);
+        assert_eq!(question_deadline(&config, Some(1_600), 1_000).unwrap(), 1_600);
+        assert_eq!(question_deadline(&config, Some(4_600), 1_000).unwrap(), 4_600);
+        assert_eq!(
+            question_deadline(&config, Some(1_000), 1_000).unwrap_err(),
+            VotingQuestionError::InvalidDeadline.into()
+        );
+        assert_eq!(
+            question_deadline(&config, Some(1_599), 1_000).unwrap_err(),
+            VotingQuestionError::VotingPeriodTooShort.into()
+        );
+        assert_eq!(
+            question_deadline(&config, Some(4_601), 1_000).unwrap_err(),
+            VotingQuestionError::VotingPeriodTooLong.into()
+        );
+
+        // The minimum can't exceed the voting period itself
+        config.min_voting_period = 3_601;
+        assert_eq!(validate_config(&config).unwrap_err(), VotingError::InvalidMinVotingPeriod.into());
+        config.min_voting_period = -1;
+        assert_eq!(validate_config(&config).unwrap_err(), VotingError::InvalidMinVotingPeriod.into());
     }
 
-    pub fn create_voting_question(
-        ctx: Context<CreateVotingQuestion>,
-        question: String,
-        options: Vec<String>,
-        deadline: i64,
-    ) -> Result<()> {
-        let community = &ctx.accounts.community;
-        let member = &ctx.accounts.member;
-        // Prevent if community is dissolved
-        if community.member_count == u32::MAX {
-            return err!(EdgeCaseError::CommunityDissolved);
-        }
-        // Prevent if member is not approved
-        if member.status != 1 {
-            return err!(EdgeCaseError::MemberInactive);
-        }
-        let max_question_len = 256;
-        let max_options = 4;
-        let min_options = 2;
-        let now = Clock::get()?.unix_timestamp;
-        if question.len() > max_question_len {
-            return err!(VotingQuestionError::QuestionTooLong);
-        }
-        if options.len() < min_options || options.len() > max_options {
-            return err!(VotingQuestionError::TooManyOptions);
-        }
-        if deadline <= now {
-            return err!(VotingQuestionError::InvalidDeadline);
+
+    fn active_community(admin: Pubkey) -> Community {
+        Community {
+            version: COMMUNITY_VERSION,
+            admins: vec![admin],
+            name: "C".to_string(),
+            description: "D".to_string(),
+            member_count: 2,
+            created_at: 0,
+            config: config(QuorumRule::None, PassThreshold::SimpleMajority),
+            question_count: 1,
+            slug: "c".to_string(),
+            status: CommunityStatus::Active,
+            pending_admin: None,
+            admin_threshold: 1,
+            admin_proposal_count: 0,
         }
-        let voting_question = &mut ctx.accounts.voting_question;
-        voting_question.community = ctx.accounts.community.key();
-        voting_question.creator = ctx.accounts.creator.key();
-        voting_question.question = question;
-        voting_question.options = options;
-        voting_question.deadline = deadline;
-        voting_question.created_at = now;
+    }
+
+    #[test]
+    fn questions_close_after_the_deadline_or_are_cancelled_before_it() {
+        let admin = Pubkey::new_unique();
+        let mut community = active_community(admin);
+        let mut voting_question = question(2);
+        voting_question.deadline = 100;
+
+        // Anyone may close once voting has ended
+        assert_eq!(
+            ensure_question_closable(&voting_question, &community, 100).unwrap_err(),
+            QuestionCloseError::VotingStillOpen.into()
+        );
+        assert!(ensure_question_closable(&voting_question, &community, 101).is_ok());
+
+        // Only the creator or a moderator may cancel, and only while voting is open
+        let creator = voting_question.creator;
+        let moderator = role_permissions(MemberRole::Moderator);
+        assert!(ensure_question_cancellable(&voting_question, &community, creator, 0, "typo", 100).is_ok());
+        assert!(ensure_question_cancellable(&voting_question, &community, admin, ALL_PERMISSIONS, "", 0).is_ok());
+        assert!(ensure_question_cancellable(&voting_question, &community, Pubkey::new_unique(), moderator, "", 0).is_ok());
+        let member_permissions = role_permissions(MemberRole::Member);
+        assert_eq!(
+            ensure_question_cancellable(&voting_question, &community, Pubkey::new_unique(), member_permissions, "", 0)
+                .unwrap_err(),
+            QuestionCloseError::NotAuthorized.into()
+        );
+        assert_eq!(
+            ensure_question_cancellable(&voting_question, &community, admin, ALL_PERMISSIONS, "", 101).unwrap_err(),
+            QuestionCloseError::VotingEnded.into()
+        );
+        let reason = "x".repeat(MAX_CANCEL_REASON_LEN + 1);
+        assert_eq!(
+            ensure_question_cancellable(&voting_question, &community, admin, ALL_PERMISSIONS, &reason, 0).unwrap_err(),
+            QuestionCloseError::ReasonTooLong.into()
+        );
+
+        voting_question.is_active = false;
+        assert_eq!(
+            ensure_question_closable(&voting_question, &community, 101).unwrap_err(),
+            QuestionCloseError::AlreadyClosed.into()
+        );
+        assert_eq!(
+            ensure_question_cancellable(&voting_question, &community, admin, ALL_PERMISSIONS, "", 0).unwrap_err(),
+            QuestionCloseError::AlreadyClosed.into()
+        );
+
         voting_question.is_active = true;
-        emit!(VotingQuestionCreated {
-            community: ctx.accounts.community.key(),
-            question: ctx.accounts.voting_question.key(),
-            creator: ctx.accounts.creator.key(),
-            deadline: deadline,
-            timestamp: now,
-        });
-        Ok(())
+        community.status = CommunityStatus::Dissolved;
+        assert_eq!(
+            ensure_question_closable(&voting_question, &community, 101).unwrap_err(),
+            EdgeCaseError::CommunityDissolved.into()
+        );
     }
 
-    pub fn cast_vote(
-        ctx: Context<CastVote>,
-        selected_option: u8,
-    ) -> Result<()> {
-        let voting_question = &mut ctx.accounts.voting_question;
-        let member = &ctx.accounts.member;
-        let community = &ctx.accounts.community;
-        let now = Clock::get()?.unix_timestamp;
-        // Prevent if community is dissolved
-        if community.member_count == u32::MAX {
-            return err!(EdgeCaseError::CommunityDissolved);
-        }
-        // Prevent if member is not approved
-        if member.status != 1 {
-            return err!(EdgeCaseError::MemberInactive);
-        }
-        if !voting_question.is_active {
-            return err!(VotingQuestionError::NotActive);
-        }
-        if now > voting_question.deadline {
-            return err!(VotingQuestionError::DeadlinePassed);
-        }
-        if selected_option as usize >= voting_question.options.len() {
-            return err!(VotingQuestionError::InvalidOption);
-        }
-        let vote = &mut ctx.accounts.vote;
-        vote.question = voting_question.key();
-        vote.voter = ctx.accounts.voter.key();
-        vote.selected_option = selected_option;
-        vote.voted_at = now;
-        emit!(VoteCast {
-            question: vote.question,
-            voter: vote.voter,
-            selected_option: vote.selected_option,
-            timestamp: now,
-        });
-        Ok(())
+
+    #[test]
+    fn scheduled_questions_open_at_their_start_time() {
+        let mut voting_question = question(2);
+        voting_question.starts_at = Some(50);
+        voting_question.deadline = 100;
+        assert_eq!(
+            ensure_voting_window(&voting_question, 49).unwrap_err(),
+            VotingQuestionError::VotingNotStarted.into()
+        );
+        assert!(ensure_voting_window(&voting_question, 50).is_ok());
+        assert!(ensure_voting_window(&voting_question, 100).is_ok());
+        assert_eq!(
+            ensure_voting_window(&voting_question, 101).unwrap_err(),
+            VotingQuestionError::DeadlinePassed.into()
+        );
+
+        // The voting period runs from the start time, not from creation
+        let config = config(QuorumRule::None, PassThreshold::SimpleMajority);
+        assert_eq!(question_deadline(&config, None, 5_000).unwrap(), 8_600);
+        assert_eq!(
+            question_deadline(&config, Some(5_000), 5_000).unwrap_err(),
+            VotingQuestionError::InvalidDeadline.into()
+        );
     }
 
-    pub fn close_voting_question(
-        ctx: Context<CloseVotingQuestion>,
-    ) -> Result<()> {
-        let voting_question = &mut ctx.accounts.voting_question;
-        let now = Clock::get()?.unix_timestamp;
-        if now < voting_question.deadline {
-            return err!(VotingQuestionError::DeadlinePassed);
-        }
-        voting_question.is_active = false;
-        emit!(VotingQuestionClosed {
-            question: ctx.accounts.voting_question.key(),
-            closer: ctx.accounts.closer.key(),
-            timestamp: now,
-        });
-        Ok(())
+    #[test]
+    fn role_presets_nest() {
+        let member = role_permissions(MemberRole::Member);
+        let moderator = role_permissions(MemberRole::Moderator);
+        let admin = role_permissions(MemberRole::Admin);
+        assert_eq!(member & !moderator, 0);
+        assert_eq!(moderator & !admin, 0);
+        assert_eq!(admin, ALL_PERMISSIONS);
+        assert_eq!(moderator & (PERMISSION_EDIT_CONFIG | PERMISSION_MANAGE_ROLES), 0);
     }
 
-    pub fn remove_member(
-        ctx: Context<RemoveMember>,
-    ) -> Result<()> {
-        let community = &mut ctx.accounts.community;
-        let member = &mut ctx.accounts.member;
-        let admin = &ctx.accounts.admin;
-        if community.admin != *admin.key {
-            return err!(AdminActionError::NotAdmin);
-        }
-        if member.status != 1 {
-            return err!(AdminActionError::NotApproved);
-        }
-        // Mark member as removed (status = 3)
-        member.status = 3;
-        // Decrement member_count safely
-        if community.member_count > 0 {
-            community.member_count -= 1;
-        }
-        msg!("Member {} removed by admin {}", member.wallet, admin.key);
-        let now = Clock::get()?.unix_timestamp;
-        emit!(MemberRemoved {
-            community: ctx.accounts.community.key(),
-            member_wallet: member.wallet,
-            admin: *admin.key,
-            timestamp: now,
-        });
-        Ok(())
+    #[test]
+    fn permissions_come_from_the_admin_or_an_approved_member_account() {
+        let admin = Pubkey::new_unique();
+        let community = active_community(admin);
+        let community_key = Pubkey::new_unique();
+        assert_eq!(permissions_of(&community, community_key, admin, None), ALL_PERMISSIONS);
+
+        let mut moderator = member(community_key);
+        moderator.role = MemberRole::Moderator;
+        moderator.permissions = role_permissions(MemberRole::Moderator);
+        let wallet = moderator.wallet;
+        assert_eq!(permissions_of(&community, community_key, wallet, Some(&moderator)), moderator.permissions);
+        assert_eq!(permissions_of(&community, community_key, wallet, None), 0);
+        // Someone else's account, another community's or a member no longer approved grants nothing
+        assert_eq!(permissions_of(&community, community_key, Pubkey::new_unique(), Some(&moderator)), 0);
+        assert_eq!(permissions_of(&community, Pubkey::new_unique(), wallet, Some(&moderator)), 0);
+        moderator.status = MemberStatus::Removed;
+        assert_eq!(permissions_of(&community, community_key, wallet, Some(&moderator)), 0);
     }
 
-    pub fn dissolve_community(
-        ctx: Context<DissolveCommunity>,
-    ) -> Result<()> {
-        let community = &mut ctx.accounts.community;
-        let admin = &ctx.accounts.admin;
-        if community.admin != *admin.key {
-            return err!(AdminActionError::NotAdmin);
+    #[test]
+    fn grants_need_manage_roles_and_never_exceed_the_granter() {
+        let moderator = role_permissions(MemberRole::Moderator);
+        assert!(authorize_grant(ALL_PERMISSIONS, moderator).is_ok());
+        assert_eq!(
+            authorize_grant(moderator, PERMISSION_CREATE_QUESTIONS).unwrap_err(),
+            PermissionError::MissingPermission.into()
+        );
+        let role_manager = PERMISSION_MANAGE_ROLES | PERMISSION_CREATE_QUESTIONS;
+        assert!(authorize_grant(role_manager, PERMISSION_CREATE_QUESTIONS).is_ok());
+        assert_eq!(
+            authorize_grant(role_manager, role_permissions(MemberRole::Admin)).unwrap_err(),
+            PermissionError::PermissionEscalation.into()
+        );
+    }
+
+    #[test]
+    fn admin_set_stays_bounded_and_never_empty() {
+        let creator = Pubkey::new_unique();
+        let mut community = active_community(creator);
+        let mut candidate = member(Pubkey::new_unique());
+        add_to_admins(&mut community, &mut candidate).unwrap();
+        assert_eq!(community.admins, vec![creator, candidate.wallet]);
+        assert_eq!((candidate.role, candidate.permissions), (MemberRole::Admin, ALL_PERMISSIONS));
+        assert!(is_admin(&community, &candidate.wallet));
+        assert_eq!(
+            add_to_admins(&mut community, &mut candidate).unwrap_err(),
+            AdminSetError::AlreadyAdmin.into()
+        );
+
+        let mut pending = member(Pubkey::new_unique());
+        pending.status = MemberStatus::Pending;
+        assert_eq!(
+            add_to_admins(&mut community, &mut pending).unwrap_err(),
+            RoleManagementError::NotApprovedMember.into()
+        );
+        while community.admins.len() < MAX_ADMINS {
+            add_to_admins(&mut community, &mut member(Pubkey::new_unique())).unwrap();
         }
-        // Mark community as dissolved by setting member_count to u32::MAX (sentinel)
-        if community.member_count == u32::MAX {
-            return err!(AdminActionError::CommunityDissolved);
+        assert_eq!(
+            add_to_admins(&mut community, &mut member(Pubkey::new_unique())).unwrap_err(),
+            AdminSetError::TooManyAdmins.into()
+        );
+
+        // Admins come off one by one, but the last one stays
+        assert_eq!(
+            remove_from_admins(&mut community, Pubkey::new_unique()).unwrap_err(),
+            AdminSetError::NotAnAdmin.into()
+        );
+        remove_from_admins(&mut community, creator).unwrap();
+        assert!(!is_admin(&community, &creator));
+        while community.admins.len() > 1 {
+            let wallet = community.admins[0];
+            remove_from_admins(&mut community, wallet).unwrap();
         }
-        community.member_count = u32::MAX;
-        msg!("Community {} dissolved by admin {}", community.key(), admin.key);
-        let now = Clock::get()?.unix_timestamp;
-        emit!(CommunityDissolved {
-            community: ctx.accounts.community.key(),
-            admin: *admin.key,
-            timestamp: now,
-        });
-        Ok(())
+        let last = community.admins[0];
+        assert_eq!(remove_from_admins(&mut community, last).unwrap_err(), AdminSetError::LastAdmin.into());
+        assert_eq!(community.admins, vec![last]);
     }
 
-    pub fn update_community_config(
-        ctx: Context<UpdateCommunityConfig>,
-        new_config: CommunityConfig,
-    ) -> Result<()> {
-        // Validation for the new config
-        if new_config.voting_period <= 0 {
-            return err!(VotingError::InvalidVotingPeriod);
-        }
-        if new_config.max_options < 2 || new_config.max_options > 4 {
-            return err!(VotingError::InvalidMaxOptions);
+    #[test]
+    fn admin_transfers_expire_and_can_then_be_replaced() {
+        let admin = Pubkey::new_unique();
+        let mut community = active_community(admin);
+        let heir = Pubkey::new_unique();
+        assert_eq!(
+            propose_transfer(&mut community, Pubkey::new_unique(), heir, None, 10).unwrap_err(),
+            AdminActionError::NotAdmin.into()
+        );
+        assert_eq!(
+            propose_transfer(&mut community, admin, heir, Some(10), 10).unwrap_err(),
+            AdminTransferError::InvalidExpiry.into()
+        );
+        propose_transfer(&mut community, admin, heir, Some(20), 10).unwrap();
+
+        // A live offer blocks a new one, and only the proposed wallet may accept it
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            propose_transfer(&mut community, admin, other, None, 20).unwrap_err(),
+            AdminTransferError::TransferPending.into()
+        );
+        assert_eq!(
+            accept_transfer(&mut community.clone(), other, 20).unwrap_err(),
+            AdminTransferError::NotPendingAdmin.into()
+        );
+        assert!(accept_transfer(&mut community.clone(), heir, 20).is_ok());
+
+        // Once expired it can't be accepted, but it can be replaced
+        assert!(transfer_expired(community.pending_admin.as_ref().unwrap(), 21));
+        assert_eq!(
+            accept_transfer(&mut community, heir, 21).unwrap_err(),
+            AdminTransferError::TransferExpired.into()
+        );
+        propose_transfer(&mut community, admin, other, None, 21).unwrap();
+        assert_eq!(community.pending_admin.as_ref().map(|transfer| transfer.to), Some(other));
+
+        // Without an expiry the offer stands until accepted, and the seat changes hands
+        let transfer = accept_transfer(&mut community, other, i64::MAX).unwrap();
+        assert_eq!(transfer.from, admin);
+        assert_eq!(community.admins, vec![other]);
+        assert!(community.pending_admin.is_none());
+        assert_eq!(
+            accept_transfer(&mut community, other, i64::MAX).unwrap_err(),
+            AdminTransferError::NoPendingTransfer.into()
+        );
+    }
+
+    fn proposal(community: &Community, action: CommunityAction) -> AdminProposal {
+        AdminProposal {
+            community: Pubkey::new_unique(),
+            index: 0,
+            proposer: community.admins[0],
+            action,
+            approvals: vec![community.admins[0]],
+            created_at: 0,
+            expires_at: 100,
         }
+    }
 
-        let community = &mut ctx.accounts.community;
-        community.config = new_config;
-        
-        msg!("Community config updated by admin {}", ctx.accounts.admin.key);
-        Ok(())
+    #[test]
+    fn admin_proposals_need_threshold_approvals_from_current_admins() {
+        let (first, second, third) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut community = active_community(first);
+        community.admins = vec![first, second, third];
+        community.admin_threshold = 2;
+        assert_eq!(ensure_no_admin_threshold(&community).unwrap_err(), MultisigError::ProposalRequired.into());
+        let mut proposal = proposal(&community, CommunityAction::DissolveCommunity);
+        assert_eq!(
+            ensure_proposal_executable(&community, &proposal, 10).unwrap_err(),
+            MultisigError::ThresholdNotMet.into()
+        );
+
+        assert_eq!(
+            record_approval(&community, &mut proposal, first, 10).unwrap_err(),
+            MultisigError::AlreadyApproved.into()
+        );
+        assert_eq!(
+            record_approval(&community, &mut proposal, Pubkey::new_unique(), 10).unwrap_err(),
+            AdminActionError::NotAdmin.into()
+        );
+        assert_eq!(
+            record_approval(&community, &mut proposal, second, 101).unwrap_err(),
+            MultisigError::ProposalExpired.into()
+        );
+        record_approval(&community, &mut proposal, second, 10).unwrap();
+        assert!(ensure_proposal_executable(&community, &proposal, 100).is_ok());
+        assert_eq!(
+            ensure_proposal_executable(&community, &proposal, 101).unwrap_err(),
+            MultisigError::ProposalExpired.into()
+        );
+
+        // An approver who loses their seat no longer counts, and is dropped on the next approval
+        community.admins = vec![first, third];
+        assert_eq!(
+            ensure_proposal_executable(&community, &proposal, 10).unwrap_err(),
+            MultisigError::ThresholdNotMet.into()
+        );
+        record_approval(&community, &mut proposal, third, 10).unwrap();
+        assert_eq!(proposal.approvals, vec![first, third]);
+        assert!(ensure_proposal_executable(&community, &proposal, 10).is_ok());
+
+        // The admin set can't shrink below the threshold
+        assert_eq!(remove_from_admins(&mut community, third).unwrap_err(), MultisigError::BelowThreshold.into());
+        assert_eq!(
+            validate_community_action(&community, &CommunityAction::SetAdminThreshold { threshold: 3 }).unwrap_err(),
+            MultisigError::InvalidThreshold.into()
+        );
     }
 
-    pub fn change_member_role(
-        ctx: Context<ChangeMemberRole>,
-        new_role: u8, // 0 = member, 1 = admin
-    ) -> Result<()> {
-        let community = &mut ctx.accounts.community;
-        let member = &mut ctx.accounts.member;
-        let admin = &ctx.accounts.admin;
-        let now = Clock::get()?.unix_timestamp;
+    #[test]
+    fn executed_proposals_apply_their_action_to_the_named_member() {
+        let admin = Pubkey::new_unique();
+        let mut community = active_community(admin);
+        community.member_count = 2;
+        let community_key = Pubkey::new_unique();
+        let mut target = member(community_key);
+        let wallet = target.wallet;
 
-        // Only the current admin can change roles
-        if community.admin != *admin.key {
-            return err!(RoleManagementError::NotAdmin);
-        }
-        // Only approved members can be promoted/demoted
-        if member.status != 1 {
-            return err!(RoleManagementError::NotApprovedMember);
-        }
-        // No-op if already the desired role
-        if member.role == new_role {
-            return err!(RoleManagementError::AlreadyRole);
+        // The targeted member's account must come along, and must be theirs
+        let action = CommunityAction::AddAdmin { wallet };
+        assert_eq!(
+            execute_community_action(&mut community, community_key, None, &action, admin, 0).unwrap_err(),
+            MultisigError::MemberAccountRequired.into()
+        );
+        let mut other = member(community_key);
+        assert_eq!(
+            execute_community_action(&mut community, community_key, Some(&mut other), &action, admin, 0).unwrap_err(),
+            MultisigError::WrongMember.into()
+        );
+        execute_community_action(&mut community, community_key, Some(&mut target), &action, admin, 0).unwrap();
+        assert_eq!(community.admins, vec![admin, wallet]);
+        assert_eq!(target.permissions, ALL_PERMISSIONS);
+
+        let action = CommunityAction::SetAdminThreshold { threshold: 2 };
+        execute_community_action(&mut community, community_key, None, &action, admin, 0).unwrap();
+        assert_eq!(community.admin_threshold, 2);
+
+        let action = CommunityAction::ChangeMemberRole { wallet: other.wallet, role: MemberRole::Moderator };
+        execute_community_action(&mut community, community_key, Some(&mut other), &action, admin, 0).unwrap();
+        assert_eq!((other.role, other.permissions), (MemberRole::Moderator, role_permissions(MemberRole::Moderator)));
+        let action = CommunityAction::SetMemberPermissions { wallet: other.wallet, permissions: PERMISSION_CREATE_QUESTIONS };
+        execute_community_action(&mut community, community_key, Some(&mut other), &action, admin, 0).unwrap();
+        assert_eq!(other.permissions, PERMISSION_CREATE_QUESTIONS);
+
+        let action = CommunityAction::RemoveMember { wallet: other.wallet };
+        execute_community_action(&mut community, community_key, Some(&mut other), &action, admin, 0).unwrap();
+        assert_eq!((other.status, community.member_count), (MemberStatus::Removed, 1));
+
+        // Admins come off with RemoveAdmin, never RemoveMember
+        let action = CommunityAction::RemoveMember { wallet };
+        assert_eq!(
+            execute_community_action(&mut community, community_key, Some(&mut target), &action, admin, 0).unwrap_err(),
+            AdminSetError::UseAdminInstructions.into()
+        );
+        community.admin_threshold = 1;
+        let action = CommunityAction::RemoveAdmin { wallet };
+        execute_community_action(&mut community, community_key, None, &action, admin, 0).unwrap();
+        assert_eq!(community.admins, vec![admin]);
+
+        execute_community_action(&mut community, community_key, None, &CommunityAction::DissolveCommunity, admin, 0).unwrap();
+        assert_eq!(community.status, CommunityStatus::Dissolved);
+        assert_eq!(
+            execute_community_action(&mut community, community_key, None, &CommunityAction::DissolveCommunity, admin, 0)
+                .unwrap_err(),
+            AdminActionError::CommunityDissolved.into()
+        );
+    }
+
+    #[test]
+    fn admin_proposal_indexes_fail_to_overflow() {
+        let mut community = active_community(Pubkey::new_unique());
+        assert_eq!(next_admin_proposal_index(&mut community).unwrap(), 0);
+        community.admin_proposal_count = u64::MAX;
+        assert_eq!(next_admin_proposal_index(&mut community).unwrap_err(), VotingError::CounterOverflow.into());
+    }
+
+    fn action_result(outcome: QuestionOutcome, winning_options: Vec<u8>) -> QuestionResult {
+        QuestionResult {
+            question: Pubkey::new_unique(),
+            community: Pubkey::new_unique(),
+            winning_options,
+            vote_counts: vec![2, 1],
+            total_ballots: 3,
+            total_weight: 3,
+            eligible_voters: 3,
+            turnout_bps: 10_000,
+            is_tie: false,
+            outcome,
+            finalized_at: 0,
+            runoff_rounds: vec![],
+            unrevealed_commits: 0,
         }
-        // If promoting to admin, update both member.role and community.admin
-        if new_role == 1 {
-            member.role = 1;
-            community.admin = member.wallet;
-        } else {
-            // If demoting self, prevent unless another admin is assigned
-            if member.wallet == *admin.key {
-                return err!(RoleManagementError::CannotDemoteSelf);
-            }
-            member.role = 0;
+    }
+
+    #[test]
+    fn action_questions_are_binding_approve_reject_votes() {
+        let mut community = active_community(Pubkey::new_unique());
+        let action = CommunityAction::DissolveCommunity;
+        let day = MIN_ACTION_VOTING_PERIOD;
+        assert_eq!(
+            validate_action_question(&community, &action, BallotType::SingleChoice, 2, 0, day).unwrap_err(),
+            GovernanceError::QuorumRequired.into()
+        );
+        community.config.quorum = QuorumRule::Absolute { min_ballots: 1 };
+        assert!(validate_action_question(&community, &action, BallotType::SingleChoice, 2, 0, day).is_ok());
+        assert_eq!(
+            validate_action_question(&community, &action, BallotType::SingleChoice, 3, 0, day).unwrap_err(),
+            GovernanceError::InvalidActionQuestion.into()
+        );
+        assert_eq!(
+            validate_action_question(&community, &action, BallotType::RankedChoice, 2, 0, day).unwrap_err(),
+            GovernanceError::InvalidActionQuestion.into()
+        );
+        assert_eq!(
+            validate_action_question(&community, &action, BallotType::SingleChoice, 2, 1, day).unwrap_err(),
+            GovernanceError::ActionVotingPeriodTooShort.into()
+        );
+
+        // Proposing actions is an admin permission unless granted explicitly
+        assert_eq!(role_permissions(MemberRole::Moderator) & PERMISSION_PROPOSE_ACTIONS, 0);
+        assert_ne!(role_permissions(MemberRole::Admin) & PERMISSION_PROPOSE_ACTIONS, 0);
+    }
+
+    #[test]
+    fn passed_actions_execute_once_within_their_window() {
+        let mut voting_question = question(2);
+        voting_question.deadline = 100;
+        let passed = action_result(QuestionOutcome::Passed, vec![0]);
+        assert_eq!(
+            executable_action(&voting_question, &passed, 101).err().unwrap(),
+            GovernanceError::NoAction.into()
+        );
+        assert!(ensure_action_settled(&voting_question, &passed, 101).is_ok());
+
+        voting_question.action = Some(CommunityAction::SetAdminThreshold { threshold: 1 });
+        let last_moment = 100 + ACTION_EXECUTION_WINDOW;
+        assert!(executable_action(&voting_question, &passed, last_moment).is_ok());
+        // Until then the question can't be closed out from under its action
+        assert_eq!(
+            ensure_action_settled(&voting_question, &passed, last_moment).unwrap_err(),
+            GovernanceError::ActionPending.into()
+        );
+
+        // Once the window closes the action lapses and the question can be closed
+        assert_eq!(
+            executable_action(&voting_question, &passed, last_moment + 1).err().unwrap(),
+            GovernanceError::ExecutionWindowClosed.into()
+        );
+        assert!(ensure_action_settled(&voting_question, &passed, last_moment + 1).is_ok());
+
+        // Secret ballots count the window from the reveal deadline
+        voting_question.reveal_deadline = Some(200);
+        assert!(executable_action(&voting_question, &passed, last_moment + 1).is_ok());
+        voting_question.reveal_deadline = None;
+
+        // Rejected, failed or tied votes approve nothing
+        for result in [
+            action_result(QuestionOutcome::Passed, vec![1]),
+            action_result(QuestionOutcome::Failed, vec![0]),
+            action_result(QuestionOutcome::QuorumNotMet, vec![0]),
+            QuestionResult { is_tie: true, ..action_result(QuestionOutcome::Passed, vec![0, 1]) },
+        ] {
+            assert_eq!(
+                executable_action(&voting_question, &result, 101).err().unwrap(),
+                GovernanceError::NotApproved.into()
+            );
+            assert!(ensure_action_settled(&voting_question, &result, 101).is_ok());
         }
-        emit!(MemberRoleChanged {
-            community: community.key(),
-            member_wallet: member.wallet,
-            new_role,
-            changed_by: *admin.key,
-            timestamp: now,
-        });
-        Ok(())
+
+        voting_question.action_executed = true;
+        assert_eq!(
+            executable_action(&voting_question, &passed, 101).err().unwrap(),
+            GovernanceError::AlreadyExecuted.into()
+        );
+        assert!(ensure_action_settled(&voting_question, &passed, 101).is_ok());
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize {}
+    #[test]
+    fn replacing_an_admin_hands_over_their_seat() {
+        let (old_admin, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut community = active_community(old_admin);
+        community.admins.push(other);
+        community.pending_admin = Some(AdminTransfer { from: old_admin, to: other, proposed_at: 0, expires_at: None });
+        let community_key = Pubkey::new_unique();
+
+        let new_admin = Pubkey::new_unique();
+        let taken = CommunityAction::ReplaceAdmin { old_admin, new_admin: other };
+        assert_eq!(
+            execute_community_action(&mut community, community_key, None, &taken, other, 0).unwrap_err(),
+            AdminSetError::AlreadyAdmin.into()
+        );
+        let action = CommunityAction::ReplaceAdmin { old_admin, new_admin };
+        execute_community_action(&mut community, community_key, None, &action, other, 0).unwrap();
+        assert_eq!(community.admins, vec![new_admin, other]);
+        // The unseated admin's pending transfer goes with their seat
+        assert!(community.pending_admin.is_none());
+        assert_eq!(
+            execute_community_action(&mut community, community_key, None, &action, other, 0).unwrap_err(),
+            AdminSetError::NotAnAdmin.into()
+        );
+    }
+}
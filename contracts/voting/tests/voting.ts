@@ -2,6 +2,7 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Voting } from "../target/types/voting";
 import { assert } from "chai";
+import { createHash } from "crypto";
 
 describe("voting", () => {
   // Configure the client to use the local cluster.
@@ -12,41 +13,71 @@ describe("voting", () => {
   // Keypairs and PDAs
   const admin = provider.wallet;
   const member1 = anchor.web3.Keypair.generate();
-  const community = anchor.web3.Keypair.generate();
+  // Communities live at a PDA derived from the SHA-256 of their normalized name
+  const [community] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("community"), createHash("sha256").update("test-community").digest()],
+    program.programId
+  );
+
+  const memberPdaFor = (wallet: anchor.web3.PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("member"), community.toBuffer(), wallet.toBuffer()],
+      program.programId
+    )[0];
+
+  // Questions are addressed by their position in the community's question sequence
+  const questionPdaFor = (index: number) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("question"),
+        community.toBuffer(),
+        new anchor.BN(index).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
 
   it("Creates a community", async () => {
     const name = "Test Community";
     const description = "A community for testing.";
     const config = {
       votingPeriod: new anchor.BN(3600), // 1 hour
+      minVotingPeriod: new anchor.BN(0),
       maxOptions: 4,
+      quorum: { none: {} },
+      passThreshold: { simpleMajority: {} },
+      voteWeighting: { onePerMember: {} },
     };
 
     await program.methods
       .createCommunity(name, description, config)
       .accounts({
-        community: community.publicKey,
+        community: community,
         admin: admin.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
-      .signers([community])
       .rpc();
 
     const communityAccount = await program.account.community.fetch(
-      community.publicKey
+      community
     );
 
     assert.equal(communityAccount.name, name);
+    assert.equal(communityAccount.slug, "test-community");
     assert.equal(communityAccount.description, description);
-    assert.ok(communityAccount.admin.equals(admin.publicKey));
+    assert.equal(communityAccount.admins.length, 1);
+    assert.ok(communityAccount.admins[0].equals(admin.publicKey));
     assert.equal(communityAccount.memberCount, 1); // Admin is the first member
+    assert.deepEqual(communityAccount.status, { active: {} });
+    assert.equal(communityAccount.version, 1);
+    assert.equal(communityAccount.adminThreshold, 1);
+    assert.equal(communityAccount.pendingAdmin, null);
   });
 
   it("Member requests to join (pending)", async () => {
     const [memberPda, _] = await anchor.web3.PublicKey.findProgramAddress(
       [
         Buffer.from("member"),
-        community.publicKey.toBuffer(),
+        community.toBuffer(),
         member1.publicKey.toBuffer(),
       ],
       program.programId
@@ -55,7 +86,7 @@ describe("voting", () => {
     await program.methods
       .joinCommunity()
       .accounts({
-        community: community.publicKey,
+        community: community,
         member: memberPda,
         user: member1.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
@@ -64,16 +95,16 @@ describe("voting", () => {
       .rpc();
 
     const memberAccount = await program.account.member.fetch(memberPda);
-    assert.ok(memberAccount.community.equals(community.publicKey));
+    assert.ok(memberAccount.community.equals(community));
     assert.ok(memberAccount.wallet.equals(member1.publicKey));
-    assert.equal(memberAccount.status, 0); // 0 = Pending
+    assert.deepEqual(memberAccount.status, { pending: {} });
   });
 
   it("Admin approves member", async () => {
     const [memberPda, _] = await anchor.web3.PublicKey.findProgramAddress(
       [
         Buffer.from("member"),
-        community.publicKey.toBuffer(),
+        community.toBuffer(),
         member1.publicKey.toBuffer(),
       ],
       program.programId
@@ -82,17 +113,19 @@ describe("voting", () => {
     await program.methods
       .approveMember(true) // true for approve
       .accounts({
-        community: community.publicKey,
+        community: community,
         member: memberPda,
-        admin: admin.publicKey,
+        authority: admin.publicKey,
+        authorityMember: null,
       })
       .rpc();
 
     const memberAccount = await program.account.member.fetch(memberPda);
-    assert.equal(memberAccount.status, 1); // 1 = Approved
+    assert.deepEqual(memberAccount.status, { approved: {} });
+    assert.equal(memberAccount.permissions, 1 << 2); // PERMISSION_CREATE_QUESTIONS
 
     const communityAccount = await program.account.community.fetch(
-      community.publicKey
+      community
     );
     assert.equal(communityAccount.memberCount, 2); // Admin + new member
   });
@@ -102,22 +135,14 @@ describe("voting", () => {
     const options = ["Red", "Green", "Blue"];
     const deadline = new anchor.BN(Date.now() / 1000 + 3); // 3 seconds from now
 
-    const [questionPda, _] = await anchor.web3.PublicKey.findProgramAddress(
-        [
-            Buffer.from("question"),
-            community.publicKey.toBuffer(),
-            member1.publicKey.toBuffer(),
-            deadline.toBuffer("le", 8),
-        ],
-        program.programId
-    );
-
+    const questionPda = questionPdaFor(0);
 
     await program.methods
-      .createVotingQuestion(question, options, deadline)
+      .createVotingQuestion(question, options, null, deadline, { singleChoice: {} }, new anchor.BN(0), null)
       .accounts({
         votingQuestion: questionPda,
-        community: community.publicKey,
+        community: community,
+        member: memberPdaFor(member1.publicKey),
         creator: member1.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
@@ -130,16 +155,16 @@ describe("voting", () => {
     assert.equal(questionAccount.question, question);
     assert.deepEqual(questionAccount.options, options);
     assert.ok(questionAccount.creator.equals(member1.publicKey));
+    assert.equal(questionAccount.index.toNumber(), 0);
+
+    const communityAccount = await program.account.community.fetch(
+      community
+    );
+    assert.equal(communityAccount.questionCount.toNumber(), 1);
   });
 
   it("Approved member casts a vote", async () => {
-    // We need to re-fetch the question PDA since it depends on a timestamp
-    // A better approach in a real test suite would be to use a fixed clock
-    const questionAccounts = await program.account.votingQuestion.all([
-        { memcmp: { offset: 8, bytes: community.publicKey.toBase58() } },
-        { memcmp: { offset: 40, bytes: member1.publicKey.toBase58() } }
-    ]);
-    const questionPda = questionAccounts[0].publicKey;
+    const questionPda = questionPdaFor(0);
 
     const [votePda, _] = await anchor.web3.PublicKey.findProgramAddress(
         [
@@ -150,12 +175,23 @@ describe("voting", () => {
         program.programId
     );
 
+    const [memberPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+            Buffer.from("member"),
+            community.publicKey.toBuffer(),
+            member1.publicKey.toBuffer(),
+        ],
+        program.programId
+    );
+
     const selectedOption = 1; // "Green"
 
     await program.methods
         .castVote(selectedOption)
         .accounts({
             votingQuestion: questionPda,
+            member: memberPda,
+            community: community.publicKey,
             vote: votePda,
             voter: member1.publicKey,
             systemProgram: anchor.web3.SystemProgram.programId,
@@ -167,22 +203,29 @@ describe("voting", () => {
     assert.ok(voteAccount.question.equals(questionPda));
     assert.ok(voteAccount.voter.equals(member1.publicKey));
     assert.equal(voteAccount.selectedOption, selectedOption);
+    assert.equal(voteAccount.weight.toNumber(), 1); // One member, one vote
+    assert.deepEqual(voteAccount.selectedOptions, Buffer.from([selectedOption]));
+
+    // The question carries the running tally
+    const questionAccount = await program.account.votingQuestion.fetch(questionPda);
+    assert.deepEqual(
+      questionAccount.voteCounts.map((c) => c.toNumber()),
+      [0, 1, 0]
+    );
+    assert.equal(questionAccount.totalBallots.toNumber(), 1);
   });
 
   it("Anyone can close voting question after deadline", async () => {
     // Wait for the deadline to pass
     await new Promise(resolve => setTimeout(resolve, 4000));
     
-    const questionAccounts = await program.account.votingQuestion.all([
-        { memcmp: { offset: 8, bytes: community.publicKey.toBase58() } },
-        { memcmp: { offset: 40, bytes: member1.publicKey.toBase58() } }
-    ]);
-    const questionPda = questionAccounts[0].publicKey;
+    const questionPda = questionPdaFor(0);
 
     await program.methods
         .closeVotingQuestion()
         .accounts({
             votingQuestion: questionPda,
+            community: community,
             closer: admin.publicKey,
         })
         .rpc();
@@ -190,4 +233,37 @@ describe("voting", () => {
     const questionAccount = await program.account.votingQuestion.fetch(questionPda);
     assert.equal(questionAccount.isActive, false);
   });
-}); 
\ No newline at end of file
+
+  it("Anyone can finalize a question into a result account", async () => {
+    const questionPda = questionPdaFor(0);
+
+    const [resultPda, _] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("result"), questionPda.toBuffer()],
+        program.programId
+    );
+
+    await program.methods
+        .finalizeQuestion()
+        .accounts({
+            votingQuestion: questionPda,
+            result: resultPda,
+            runoff: null,
+            runoffPayer: null,
+            payer: admin.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+    const resultAccount = await program.account.questionResult.fetch(resultPda);
+    assert.ok(resultAccount.question.equals(questionPda));
+    assert.deepEqual(resultAccount.winningOptions, Buffer.from([1]));
+    assert.equal(resultAccount.totalBallots.toNumber(), 1);
+    assert.equal(resultAccount.eligibleVoters, 2);
+    assert.equal(resultAccount.turnoutBps, 5000);
+    assert.equal(resultAccount.isTie, false);
+    assert.deepEqual(resultAccount.outcome, { passed: {} });
+
+    const questionAccount = await program.account.votingQuestion.fetch(questionPda);
+    assert.equal(questionAccount.isFinalized, true);
+  });
+});